@@ -1,9 +1,11 @@
+use std::collections::BTreeMap;
 use std::env::args;
+use std::ffi::OsString;
 use anyhow::{bail, Result};
 use std::path::{Path, PathBuf};
-use std::process::Stdio;
+use std::process::{ExitStatus, Stdio};
 use log::info;
-use tokio::io::AsyncReadExt;
+use tokio::io::{AsyncBufReadExt, BufReader};
 use tokio::process::{Child, Command};
 use tokio::sync::oneshot::Receiver;
 use tracing::{debug, info_span};
@@ -15,17 +17,32 @@ impl JavaRuntime {
     }
 
     pub async fn execute(&self, arguments: Vec<String>, game_dir: &Path) -> Result<Child> {
+        self.execute_with(arguments, game_dir, &[], &BTreeMap::new(), &[]).await
+    }
+
+    /// Start Java behind the `wrappers` commands (`gamemoderun`, `prime-run`...), the first one being the outermost,
+    /// with `environment` set and `removed_environment` unset on top of the inherited environment
+    pub async fn execute_with(
+        &self,
+        arguments: Vec<String>,
+        game_dir: &Path,
+        wrappers: &[Vec<String>],
+        environment: &BTreeMap<String, String>,
+        removed_environment: &[String],
+    ) -> Result<Child> {
         if !self.0.exists() {
             bail!("Java runtime not found at: {}", self.0.display());
         }
-        let mut command = Command::new(&self.0);
+        let mut program = wrappers.iter().flatten().map(OsString::from).collect::<Vec<_>>();
+        program.push(self.0.clone().into_os_string());
+        let mut command = Command::new(&program[0]);
+        command.args(&program[1..]);
 
 
         //DEBUG TEST
         debug!("Executing Java runtime: {}", self.0.display());
+        // The arguments hold the access token, launch logs the redacted ones
         info!("Executing Java runtime: {}", self.0.display());
-        let mut test = arguments.clone();
-        info!("Arguments: {:?}", command.args(test));
 
 
 
@@ -35,51 +52,79 @@ impl JavaRuntime {
         command.args(arguments);
         println!("Java runtime: {}", self.0.display());
 
+        for name in removed_environment {
+            command.env_remove(name);
+        }
+        command.envs(environment);
+
         command.stderr(Stdio::piped()).stdout(Stdio::piped());
 
         let child = command.spawn()?;
         Ok(child)
     }
 
-    pub async fn handle_io<D: Send + Sync>(
+    /// Forward the output of `running_task` line by line until it exits or `terminator` fires, then return its exit status
+    pub async fn handle_io<F: FnMut(OutputStream, String)>(
         &self,
         running_task: &mut Child,
-        on_stdout: fn(&D, &[u8]) -> Result<()>,
-        on_stderr: fn(&D, &[u8]) -> Result<()>,
+        mut on_line: F,
         terminator: Receiver<()>,
-        data: &D,
-    ) -> Result<()> {
-        let mut stdout = running_task.stdout.take().unwrap();
-        let mut stderr = running_task.stderr.take().unwrap();
+    ) -> Result<ExitStatus> {
+        let mut stdout = BufReader::new(running_task.stdout.take().unwrap());
+        let mut stderr = BufReader::new(running_task.stderr.take().unwrap());
 
-        let mut stdout_buf = vec![0; 1024];
-        let mut stderr_buf = vec![0; 1024];
+        let mut stdout_buf = Vec::new();
+        let mut stderr_buf = Vec::new();
+        let mut stdout_open = true;
+        let mut stderr_open = true;
+        let mut terminator_open = true;
 
         tokio::pin!(terminator);
 
         loop {
             tokio::select! {
-                read_len = stdout.read(&mut stdout_buf) => {
-                    let _ = on_stdout(&data, &stdout_buf[..read_len?]);
+                read_len = stdout.read_until(b'\n', &mut stdout_buf), if stdout_open => {
+                    match read_len? {
+                        0 => stdout_open = false,
+                        _ => on_line(OutputStream::Stdout, take_line(&mut stdout_buf)),
+                    }
                 },
-                read_len = stderr.read(&mut stderr_buf) => {
-                    let _ = on_stderr(&data, &stderr_buf[..read_len?]);
+                read_len = stderr.read_until(b'\n', &mut stderr_buf), if stderr_open => {
+                    match read_len? {
+                        0 => stderr_open = false,
+                        _ => on_line(OutputStream::Stderr, take_line(&mut stderr_buf)),
+                    }
                 },
-                _ = &mut terminator => {
+                stop = &mut terminator, if terminator_open => {
+                    // A dropped sender only means nobody can stop the game anymore
+                    if stop.is_err() {
+                        terminator_open = false;
+                        continue;
+                    }
                     running_task.kill().await?;
-                    break;
+                    return Ok(running_task.wait().await?);
                 },
-                exit_status = running_task.wait() => {
-                    let code = exit_status?.code().unwrap_or(7900); // 7900 = unwrap failed error code
-
-                    debug!("Process exited with code: {}", code);
-                    if code != 0 && code != -1073740791 { // -1073740791 = happens when the process is killed forcefully, we don't want to bail in this case
-                        bail!("Process exited with non-zero exit code: {}.", code);
-                    }
-                    break;
+                // Wait for the pipes to be drained so the last lines, often the crash, are not lost
+                exit_status = running_task.wait(), if !stdout_open && !stderr_open => {
+                    let exit_status = exit_status?;
+                    debug!("Process exited with code: {:?}", exit_status.code());
+                    return Ok(exit_status);
                 },
             }
         }
-        Ok(())
     }
-}
\ No newline at end of file
+}
+
+/// Pipe a line of output was read from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputStream {
+    Stdout,
+    Stderr,
+}
+
+/// Empty `buf` into a line without its terminator, invalid UTF-8 is replaced
+fn take_line(buf: &mut Vec<u8>) -> String {
+    let line = String::from_utf8_lossy(buf).trim_end_matches(['\r', '\n']).to_string();
+    buf.clear();
+    line
+}
@@ -11,6 +11,8 @@ use once_cell::sync::Lazy;
     use std::fmt::{Debug, Display};
     use crate::java::{find_java_binary, jre_download};
     use crate::minecraft::version::launch::Launch;
+    use crate::minecraft::auth::Authenticator;
+    use crate::minecraft::auth::offline::OfflineAuthenticator;
     use super::minecraft::version::version;
     use crate::java::distribution::JavaDistribution;
     use crate::mkdir;
@@ -68,8 +70,11 @@ use once_cell::sync::Lazy;
         
 
 
+        let profile = OfflineAuthenticator::new("Hamadi").authenticate().await.unwrap();
+
         gaïa.install_version().await.unwrap();
-        gaïa.launch(&LAUNCHER_DIRECTORY.config_dir().to_path_buf()).await;
+        let process = gaïa.launch(LAUNCHER_DIRECTORY.config_dir(), &profile).await.unwrap();
+        process.wait().await.unwrap();
         //frozenearth.install_version().await.unwrap();
         //frozenearth.launch(&LAUNCHER_DIRECTORY.config_dir().to_path_buf()).await;
         //gaïa.install_version().await.unwrap();
@@ -0,0 +1,12 @@
+use crate::minecraft::auth::error::AuthError;
+use crate::minecraft::auth::profile::UserProfile;
+
+/// Common interface of the authentication backends
+pub trait Authenticator {
+    /// Log the user in and return a launchable profile
+    async fn authenticate(&self) -> Result<UserProfile, AuthError>;
+    /// Get a fresh access token for a profile previously returned by this backend
+    async fn refresh(&self, profile: &UserProfile) -> Result<UserProfile, AuthError>;
+    /// Check whether the access token of the profile is still accepted
+    async fn validate(&self, profile: &UserProfile) -> Result<bool, AuthError>;
+}
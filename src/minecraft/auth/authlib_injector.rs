@@ -0,0 +1,208 @@
+use std::error::Error;
+use std::path::{Path, PathBuf};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use tokio::fs;
+use crate::utils::hosts::HTTP_CLIENT;
+
+/// Latest release of authlib-injector, published by its authors
+pub const AUTHLIB_INJECTOR_METADATA_URL: &str = "https://authlib-injector.yushi.moe/artifact/latest.json";
+
+#[derive(Debug, Deserialize)]
+struct ArtifactMetadata {
+    version: String,
+    download_url: String,
+    checksums: ArtifactChecksums,
+}
+
+#[derive(Debug, Deserialize)]
+struct ArtifactChecksums {
+    sha256: String,
+}
+
+/// Java agent redirecting the game authentication and skins to a Yggdrasil server
+pub struct AuthlibInjector {
+    dir: PathBuf,
+    metadata_url: String,
+}
+
+impl AuthlibInjector {
+    /// `dir` is where the jar is downloaded, e.g. `<launcher dir>/authlib-injector`
+    pub fn new(dir: &Path) -> Self {
+        Self {
+            dir: dir.to_path_buf(),
+            metadata_url: AUTHLIB_INJECTOR_METADATA_URL.to_string(),
+        }
+    }
+
+    /// Use another artifact metadata, e.g. a mirror
+    pub fn with_metadata_url(mut self, metadata_url: &str) -> Self {
+        self.metadata_url = metadata_url.to_string();
+        self
+    }
+
+    /// Path of the installed jar, downloading the latest one only when none is there
+    pub async fn install(&self) -> Result<PathBuf, Box<dyn Error + Send + Sync>> {
+        match self.get_installed().await {
+            Some(jar_path) => Ok(jar_path),
+            None => self.update().await,
+        }
+    }
+
+    /// Newest jar already downloaded, it was checked against its SHA-256 before being moved in place
+    pub async fn get_installed(&self) -> Option<PathBuf> {
+        let mut entries = fs::read_dir(&self.dir).await.ok()?;
+        let mut installed: Option<(Vec<u32>, PathBuf)> = None;
+        while let Ok(Some(entry)) = entries.next_entry().await {
+            let file_name = entry.file_name().to_string_lossy().to_string();
+            let Some(version) = file_name.strip_prefix("authlib-injector-").and_then(|name| name.strip_suffix(".jar")) else {
+                continue;
+            };
+            let version: Vec<u32> = version.split('.').map(|part| part.parse().unwrap_or(0)).collect();
+            if installed.as_ref().is_none_or(|(newest, _)| version > *newest) {
+                installed = Some((version, entry.path()));
+            }
+        }
+        installed.map(|(_, path)| path)
+    }
+
+    /// Download the latest jar if it is not already there, and return its path.
+    ///
+    /// The jar is checked against the SHA-256 of the metadata, also when it is already downloaded.
+    pub async fn update(&self) -> Result<PathBuf, Box<dyn Error + Send + Sync>> {
+        let metadata: ArtifactMetadata = HTTP_CLIENT.get(&self.metadata_url).send().await?.error_for_status()?.json().await?;
+        let jar_path = self.dir.join(format!("authlib-injector-{}.jar", metadata.version));
+
+        if jar_path.exists() && sha256(&fs::read(&jar_path).await?) == metadata.checksums.sha256 {
+            return Ok(jar_path);
+        }
+
+        let content = HTTP_CLIENT.get(&metadata.download_url).send().await?.error_for_status()?.bytes().await?;
+        let hash = sha256(&content);
+        if hash != metadata.checksums.sha256 {
+            return Err(format!(
+                "SHA256 mismatch for authlib-injector {}: expected {}, got {}",
+                metadata.version, metadata.checksums.sha256, hash
+            ).into());
+        }
+
+        fs::create_dir_all(&self.dir).await?;
+        let tmp_path = jar_path.with_extension("jar.tmp");
+        fs::write(&tmp_path, &content).await?;
+        fs::rename(&tmp_path, &jar_path).await?;
+        Ok(jar_path)
+    }
+
+    /// Download the API metadata of the Yggdrasil server at `api_url`, passed to the agent so it does not request it at startup
+    pub async fn prefetch(&self, api_url: &str) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let metadata = HTTP_CLIENT.get(api_url).send().await?.error_for_status()?.bytes().await?;
+        let path = self.get_prefetched_path(api_url);
+        fs::create_dir_all(path.parent().unwrap_or(&self.dir)).await?;
+        fs::write(&path, &metadata).await?;
+        Ok(())
+    }
+
+    /// JVM arguments to prepend to start the game against the Yggdrasil server at `api_url`.
+    ///
+    /// Nothing is downloaded, see [`Self::install`] and [`Self::prefetch`]. Without prefetched metadata the agent requests it itself.
+    pub async fn get_jvm_arguments(&self, api_url: &str) -> Result<Vec<String>, Box<dyn Error + Send + Sync>> {
+        let jar_path = self.get_installed().await.ok_or("authlib-injector is not installed")?;
+        let mut arguments = vec![format!("-javaagent:{}={}", jar_path.display(), api_url)];
+        if let Ok(metadata) = fs::read(self.get_prefetched_path(api_url)).await {
+            arguments.push(format!("-Dauthlibinjector.yggdrasil.prefetched={}", STANDARD.encode(&metadata)));
+        }
+        Ok(arguments)
+    }
+
+    fn get_prefetched_path(&self, api_url: &str) -> PathBuf {
+        self.dir.join("prefetched").join(format!("{}.json", sha256(api_url.as_bytes())))
+    }
+}
+
+fn sha256(content: &[u8]) -> String {
+    hex::encode(Sha256::digest(content))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::minecraft::auth::yggdrasil::tests::mount_yggdrasil;
+    use serde_json::json;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    const JAR: &[u8] = b"PK\x03\x04 not really a jar";
+
+    async fn mount_artifact(server: &MockServer, sha256: &str) {
+        Mock::given(method("GET"))
+            .and(path("/artifact/latest.json"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "build_number": 53,
+                "version": "1.2.5",
+                "download_url": format!("{}/artifact/53/authlib-injector-1.2.5.jar", server.uri()),
+                "checksums": { "sha256": sha256 }
+            })))
+            .mount(server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/artifact/53/authlib-injector-1.2.5.jar"))
+            .respond_with(ResponseTemplate::new(200).set_body_bytes(JAR))
+            .mount(server)
+            .await;
+    }
+
+    #[tokio::test]
+    async fn jar_is_verified_and_arguments_are_built() {
+        let server = MockServer::start().await;
+        mount_artifact(&server, &sha256(JAR)).await;
+        mount_yggdrasil(&server).await;
+
+        let dir = tempfile::tempdir().unwrap();
+        let injector = AuthlibInjector::new(dir.path()).with_metadata_url(&format!("{}/artifact/latest.json", server.uri()));
+        let api_url = format!("{}/api/yggdrasil", server.uri());
+        assert!(injector.get_jvm_arguments(&api_url).await.is_err());
+        injector.install().await.unwrap();
+        assert_eq!(injector.get_jvm_arguments(&api_url).await.unwrap().len(), 1);
+        injector.prefetch(&api_url).await.unwrap();
+        let arguments = injector.get_jvm_arguments(&api_url).await.unwrap();
+
+        let jar_path = dir.path().join("authlib-injector-1.2.5.jar");
+        assert_eq!(std::fs::read(&jar_path).unwrap(), JAR);
+        assert_eq!(arguments[0], format!("-javaagent:{}={}", jar_path.display(), api_url));
+
+        let prefetched = arguments[1].strip_prefix("-Dauthlibinjector.yggdrasil.prefetched=").unwrap();
+        let metadata: serde_json::Value = serde_json::from_slice(&STANDARD.decode(prefetched).unwrap()).unwrap();
+        assert_eq!(metadata["meta"]["serverName"], "Test server");
+    }
+
+    #[tokio::test]
+    async fn tampered_jar_is_rejected() {
+        let server = MockServer::start().await;
+        mount_artifact(&server, &sha256(b"another jar")).await;
+
+        let dir = tempfile::tempdir().unwrap();
+        let injector = AuthlibInjector::new(dir.path()).with_metadata_url(&format!("{}/artifact/latest.json", server.uri()));
+
+        assert!(injector.install().await.is_err());
+        assert!(!dir.path().join("authlib-injector-1.2.5.jar").exists());
+    }
+
+    #[tokio::test]
+    async fn installed_jar_is_used_offline() {
+        let server = MockServer::start().await;
+        let dir = tempfile::tempdir().unwrap();
+        for version in ["1.2.4", "1.10.0", "1.9.9"] {
+            std::fs::write(dir.path().join(format!("authlib-injector-{}.jar", version)), JAR).unwrap();
+        }
+        std::fs::write(dir.path().join("authlib-injector-9.0.0.jar.tmp"), JAR).unwrap();
+        let injector = AuthlibInjector::new(dir.path()).with_metadata_url(&format!("{}/artifact/latest.json", server.uri()));
+
+        assert_eq!(injector.install().await.unwrap(), dir.path().join("authlib-injector-1.10.0.jar"));
+        assert!(server.received_requests().await.unwrap().is_empty());
+
+        mount_artifact(&server, &sha256(JAR)).await;
+        assert_eq!(injector.update().await.unwrap(), dir.path().join("authlib-injector-1.2.5.jar"));
+    }
+}
@@ -0,0 +1,346 @@
+use std::path::{Path, PathBuf};
+use png::{BitDepth, ColorType, Transformations};
+use sha2::{Digest, Sha256};
+use tokio::fs;
+use crate::minecraft::auth::error::AuthError;
+use crate::minecraft::auth::profile::UserProfile;
+use crate::minecraft::auth::skins::{validate_skin_png, SkinVariant};
+use crate::utils::hosts::HTTP_CLIENT;
+
+/// Largest scale accepted, a body is then 512x1024 pixels
+const MAX_SCALE: u32 = 32;
+
+/// Part of the skin to render
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AvatarKind {
+    /// Front of the head with the hat layer, 8x8 pixels
+    Face,
+    /// Flat front view of the whole player with the outer layers, 16x32 pixels
+    Body,
+}
+
+impl AvatarKind {
+    pub fn get_name(&self) -> &'static str {
+        match self {
+            AvatarKind::Face => "face",
+            AvatarKind::Body => "body",
+        }
+    }
+}
+
+/// Renders avatars from skins and caches them on disk by skin hash
+pub struct AvatarRenderer {
+    cache_dir: PathBuf,
+}
+
+impl AvatarRenderer {
+    /// `cache_dir` is where rendered PNGs are kept, e.g. `<launcher cache dir>/avatars`
+    pub fn new(cache_dir: &Path) -> Self {
+        Self { cache_dir: cache_dir.to_path_buf() }
+    }
+
+    /// Render `skin` and return the path of the cached PNG, it is only rendered the first time
+    pub async fn render(&self, skin: &[u8], kind: AvatarKind, variant: SkinVariant, scale: u32) -> Result<PathBuf, AuthError> {
+        let hash = hex::encode(Sha256::digest(skin));
+        let file_name = match kind {
+            AvatarKind::Face => format!("face_{}.png", scale),
+            // The arms of the face are not visible, only bodies depend on the variant
+            AvatarKind::Body => format!("body_{}_{}.png", variant.get_name(), scale),
+        };
+        let path = self.cache_dir.join(hash).join(file_name);
+        if path.exists() {
+            return Ok(path);
+        }
+
+        let png = match kind {
+            AvatarKind::Face => render_face(skin, scale)?,
+            AvatarKind::Body => render_body(skin, variant, scale)?,
+        };
+        fs::create_dir_all(path.parent().unwrap()).await?;
+        fs::write(&path, png).await?;
+        Ok(path)
+    }
+
+    /// Render the skin of a profile, its `skin_url` is either an HTTP URL or a local file
+    pub async fn render_profile(&self, profile: &UserProfile, kind: AvatarKind, variant: SkinVariant, scale: u32) -> Result<PathBuf, AuthError> {
+        let skin_url = profile.skin_url.as_deref().ok_or_else(|| AuthError::InvalidSkin(format!("{} has no skin", profile.name)))?;
+        let skin = load_skin(skin_url).await?;
+        self.render(&skin, kind, variant, scale).await
+    }
+}
+
+async fn load_skin(skin_url: &str) -> Result<Vec<u8>, AuthError> {
+    if skin_url.starts_with("http://") || skin_url.starts_with("https://") {
+        let response = HTTP_CLIENT.get(skin_url).send().await?.error_for_status()?;
+        return Ok(response.bytes().await?.to_vec());
+    }
+    let path = skin_url.strip_prefix("file://").unwrap_or(skin_url);
+    Ok(fs::read(path).await?)
+}
+
+/// Front of the head with the hat layer, as a PNG of `8 * scale` pixels
+pub fn render_face(skin: &[u8], scale: u32) -> Result<Vec<u8>, AuthError> {
+    let skin = Image::decode_skin(skin)?;
+    let mut face = Image::new(8, 8);
+    face.draw(&skin, (8, 8), (8, 8), (0, 0));
+    face.draw(&skin, (40, 8), (8, 8), (0, 0));
+    face.scale(scale)?.encode()
+}
+
+/// Flat front view of the player with the outer layers, as a PNG of `16 * scale` by `32 * scale` pixels
+pub fn render_body(skin: &[u8], variant: SkinVariant, scale: u32) -> Result<Vec<u8>, AuthError> {
+    let skin = Image::decode_skin(skin)?;
+    let arm_width = match variant {
+        SkinVariant::Classic => 4,
+        SkinVariant::Slim => 3,
+    };
+
+    // (base layer, outer layer, size, position in the render)
+    let parts = [
+        ((8, 8), (40, 8), (8, 8), (4, 0)),                           // Head
+        ((20, 20), (20, 36), (8, 12), (4, 8)),                       // Body
+        ((44, 20), (44, 36), (arm_width, 12), (4 - arm_width, 8)),   // Right arm
+        ((36, 52), (52, 52), (arm_width, 12), (12, 8)),              // Left arm
+        ((4, 20), (4, 36), (4, 12), (4, 20)),                        // Right leg
+        ((20, 52), (4, 52), (4, 12), (8, 20)),                       // Left leg
+    ];
+
+    let mut body = Image::new(16, 32);
+    for (base, outer, size, position) in parts {
+        body.draw(&skin, base, size, position);
+        body.draw(&skin, outer, size, position);
+    }
+    body.scale(scale)?.encode()
+}
+
+/// RGBA image, 4 bytes per pixel
+struct Image {
+    width: u32,
+    height: u32,
+    pixels: Vec<u8>,
+}
+
+impl Image {
+    fn new(width: u32, height: u32) -> Self {
+        Self { width, height, pixels: vec![0; (width * height * 4) as usize] }
+    }
+
+    /// Decode a skin and bring it to the 64x64 layout the way the game does
+    fn decode_skin(png: &[u8]) -> Result<Self, AuthError> {
+        validate_skin_png(png)?;
+
+        let mut decoder = png::Decoder::new(png);
+        decoder.set_transformations(Transformations::normalize_to_color8());
+        let mut reader = decoder.read_info().map_err(|e| AuthError::InvalidSkin(e.to_string()))?;
+        let mut buffer = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut buffer).map_err(|e| AuthError::InvalidSkin(e.to_string()))?;
+        let buffer = &buffer[..info.buffer_size()];
+
+        let pixels: Vec<u8> = match info.color_type {
+            ColorType::Rgba => buffer.to_vec(),
+            ColorType::Rgb => buffer.chunks_exact(3).flat_map(|p| [p[0], p[1], p[2], 255]).collect(),
+            ColorType::GrayscaleAlpha => buffer.chunks_exact(2).flat_map(|p| [p[0], p[0], p[0], p[1]]).collect(),
+            ColorType::Grayscale => buffer.iter().flat_map(|&g| [g, g, g, 255]).collect(),
+            ColorType::Indexed => return Err(AuthError::InvalidSkin("unsupported palette".to_string())),
+        };
+
+        let legacy = info.height == 32;
+        let mut skin = Self::new(64, 64);
+        skin.pixels[..pixels.len()].copy_from_slice(&pixels);
+
+        if legacy {
+            skin.convert_legacy();
+        }
+        // Only the outer layers can be transparent
+        skin.set_opaque((0, 0), (32, 16));
+        skin.set_opaque((0, 16), (64, 16));
+        skin.set_opaque((16, 48), (32, 16));
+        Ok(skin)
+    }
+
+    /// 64x32 skins have no left arm and leg, they mirror the right ones
+    fn convert_legacy(&mut self) {
+        // The hat of old skins is often filled, it is only kept when it has transparent pixels
+        if !self.has_transparency((32, 0), (32, 16)) {
+            self.clear((32, 0), (32, 16));
+        }
+
+        // (source, size, offset), copied mirrored
+        let copies = [
+            ((4, 16), (4, 4), (16, 32)),
+            ((8, 16), (4, 4), (16, 32)),
+            ((0, 20), (4, 12), (24, 32)),
+            ((4, 20), (4, 12), (16, 32)),
+            ((8, 20), (4, 12), (8, 32)),
+            ((12, 20), (4, 12), (16, 32)),
+            ((44, 16), (4, 4), (-8, 32)),
+            ((48, 16), (4, 4), (-8, 32)),
+            ((40, 20), (4, 12), (0, 32)),
+            ((44, 20), (4, 12), (-8, 32)),
+            ((48, 20), (4, 12), (-16, 32)),
+            ((52, 20), (4, 12), (-8, 32)),
+        ];
+        for ((x, y), (width, height), (dx, dy)) in copies {
+            for row in 0..height {
+                for column in 0..width {
+                    let pixel = self.get(x + column, y + row);
+                    let target_x = (x as i32 + dx) as u32 + width - 1 - column;
+                    self.set(target_x, (y as i32 + dy) as u32 + row, pixel);
+                }
+            }
+        }
+    }
+
+    fn get(&self, x: u32, y: u32) -> [u8; 4] {
+        let i = ((y * self.width + x) * 4) as usize;
+        self.pixels[i..i + 4].try_into().unwrap()
+    }
+
+    fn set(&mut self, x: u32, y: u32, pixel: [u8; 4]) {
+        let i = ((y * self.width + x) * 4) as usize;
+        self.pixels[i..i + 4].copy_from_slice(&pixel);
+    }
+
+    fn region(&self, (x, y): (u32, u32), (width, height): (u32, u32)) -> impl Iterator<Item = (u32, u32)> {
+        (y..y + height).flat_map(move |row| (x..x + width).map(move |column| (column, row)))
+    }
+
+    fn has_transparency(&self, position: (u32, u32), size: (u32, u32)) -> bool {
+        self.region(position, size).any(|(x, y)| self.get(x, y)[3] < 128)
+    }
+
+    fn clear(&mut self, position: (u32, u32), size: (u32, u32)) {
+        for (x, y) in self.region(position, size).collect::<Vec<_>>() {
+            self.set(x, y, [0; 4]);
+        }
+    }
+
+    fn set_opaque(&mut self, position: (u32, u32), size: (u32, u32)) {
+        for (x, y) in self.region(position, size).collect::<Vec<_>>() {
+            let [r, g, b, _] = self.get(x, y);
+            self.set(x, y, [r, g, b, 255]);
+        }
+    }
+
+    /// Alpha-blend a region of `source` over this image
+    fn draw(&mut self, source: &Image, from: (u32, u32), size: (u32, u32), to: (u32, u32)) {
+        for (x, y) in source.region(from, size) {
+            let [r, g, b, a] = source.get(x, y);
+            let (target_x, target_y) = (to.0 + x - from.0, to.1 + y - from.1);
+            let [dr, dg, db, da] = self.get(target_x, target_y);
+
+            let alpha = a as u32;
+            let blend = |s: u8, d: u8| ((s as u32 * alpha + d as u32 * (255 - alpha)) / 255) as u8;
+            let out_alpha = (alpha + da as u32 * (255 - alpha) / 255) as u8;
+            self.set(target_x, target_y, [blend(r, dr), blend(g, dg), blend(b, db), out_alpha]);
+        }
+    }
+
+    /// Nearest neighbor upscale, pixel art must stay sharp
+    fn scale(&self, scale: u32) -> Result<Image, AuthError> {
+        if scale == 0 || scale > MAX_SCALE {
+            return Err(AuthError::InvalidSkin(format!("the scale must be between 1 and {}", MAX_SCALE)));
+        }
+        let mut scaled = Image::new(self.width * scale, self.height * scale);
+        for (x, y) in scaled.region((0, 0), (scaled.width, scaled.height)).collect::<Vec<_>>() {
+            scaled.set(x, y, self.get(x / scale, y / scale));
+        }
+        Ok(scaled)
+    }
+
+    fn encode(&self) -> Result<Vec<u8>, AuthError> {
+        let mut png = Vec::new();
+        let mut encoder = png::Encoder::new(&mut png, self.width, self.height);
+        encoder.set_color(ColorType::Rgba);
+        encoder.set_depth(BitDepth::Eight);
+        let mut writer = encoder.write_header().map_err(|e| AuthError::InvalidSkin(e.to_string()))?;
+        writer.write_image_data(&self.pixels).map_err(|e| AuthError::InvalidSkin(e.to_string()))?;
+        writer.finish().map_err(|e| AuthError::InvalidSkin(e.to_string()))?;
+        Ok(png)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SKIN: [u8; 4] = [200, 150, 100, 255];
+    const HAT: [u8; 4] = [255, 0, 0, 255];
+    const RIGHT_ARM: [u8; 4] = [0, 0, 255, 255];
+
+    /// Skin filled with one color, a hat on the top half of the face and a marked right arm column
+    fn synthetic_skin(height: u32) -> Vec<u8> {
+        let mut skin = Image::new(64, height);
+        for (x, y) in skin.region((0, 0), (64, height)).collect::<Vec<_>>() {
+            skin.set(x, y, SKIN);
+        }
+        skin.clear((32, 0), (32, 16));
+        for (x, y) in skin.region((40, 8), (8, 4)).collect::<Vec<_>>() {
+            skin.set(x, y, HAT);
+        }
+        // Outer column of the right arm front, mirrored for the left arm of legacy skins
+        for y in 20..32 {
+            skin.set(44, y, RIGHT_ARM);
+        }
+        if height == 64 {
+            skin.clear((0, 32), (64, 16));
+            skin.clear((0, 48), (16, 16));
+            skin.clear((48, 48), (16, 16));
+        }
+        skin.encode().unwrap()
+    }
+
+    fn decode(png: &[u8]) -> Image {
+        let decoder = png::Decoder::new(png);
+        let mut reader = decoder.read_info().unwrap();
+        let mut buffer = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut buffer).unwrap();
+        Image { width: info.width, height: info.height, pixels: buffer }
+    }
+
+    #[test]
+    fn face_has_the_hat_layer() {
+        let face = decode(&render_face(&synthetic_skin(64), 4).unwrap());
+        assert_eq!((face.width, face.height), (32, 32));
+        assert_eq!(face.get(0, 0), HAT);
+        assert_eq!(face.get(31, 31), SKIN);
+    }
+
+    #[test]
+    fn body_follows_the_variant() {
+        let classic = decode(&render_body(&synthetic_skin(64), SkinVariant::Classic, 1).unwrap());
+        assert_eq!((classic.width, classic.height), (16, 32));
+        assert_eq!(classic.get(0, 8), RIGHT_ARM);
+
+        // Slim arms are one pixel narrower, the outer column is left empty
+        let slim = decode(&render_body(&synthetic_skin(64), SkinVariant::Slim, 1).unwrap());
+        assert_eq!(slim.get(0, 8)[3], 0);
+        assert_eq!(slim.get(1, 8), RIGHT_ARM);
+    }
+
+    #[test]
+    fn legacy_skins_mirror_the_right_limbs() {
+        let body = decode(&render_body(&synthetic_skin(32), SkinVariant::Classic, 1).unwrap());
+        assert_eq!(body.get(0, 8), RIGHT_ARM);
+        assert_eq!(body.get(15, 8), RIGHT_ARM);
+        assert_eq!(body.get(12, 8), SKIN);
+    }
+
+    #[tokio::test]
+    async fn avatars_are_cached_by_skin_hash() {
+        let dir = tempfile::tempdir().unwrap();
+        let skin_path = dir.path().join("skin.png");
+        std::fs::write(&skin_path, synthetic_skin(64)).unwrap();
+
+        let mut profile = UserProfile::new("Steve", "uuid", "0", crate::minecraft::auth::UserType::Legacy);
+        profile.skin_url = Some(skin_path.display().to_string());
+
+        let renderer = AvatarRenderer::new(&dir.path().join("avatars"));
+        let path = renderer.render_profile(&profile, AvatarKind::Face, SkinVariant::Classic, 2).await.unwrap();
+        assert!(path.ends_with("face_2.png"));
+
+        std::fs::write(&path, b"cached").unwrap();
+        let again = renderer.render_profile(&profile, AvatarKind::Face, SkinVariant::Classic, 2).await.unwrap();
+        assert_eq!(std::fs::read(again).unwrap(), b"cached");
+    }
+}
@@ -0,0 +1,240 @@
+use serde::Deserialize;
+use serde_json::json;
+use crate::minecraft::auth::authenticator::Authenticator;
+use crate::minecraft::auth::error::AuthError;
+use crate::minecraft::auth::profile::{UserProfile, UserType};
+use crate::utils::hosts::HTTP_CLIENT;
+
+/// User returned by the Azuriom auth API
+#[derive(Debug, Clone, Deserialize)]
+pub struct AzuriomUser {
+    pub id: u64,
+    pub username: String,
+    pub uuid: String,
+    pub access_token: String,
+    #[serde(default)]
+    pub email_verified: bool,
+    #[serde(default)]
+    pub banned: bool,
+    pub role: Option<AzuriomRole>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct AzuriomRole {
+    pub name: String,
+    pub color: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct AzuriomError {
+    status: String,
+    reason: String,
+    message: Option<String>,
+}
+
+/// Authentication against an Azuriom website (`/api/auth/*`).
+///
+/// The access token of the profile is the one the AzLink/AzAuth server plugin checks.
+pub struct AzuriomAuthenticator {
+    base_url: String,
+    email: String,
+    password: String,
+    two_factor_code: Option<String>,
+}
+
+impl AzuriomAuthenticator {
+    /// `base_url` is the root of the website, e.g. `https://my-server.com`
+    pub fn new(base_url: &str, email: &str, password: &str) -> Self {
+        Self {
+            base_url: base_url.trim_end_matches('/').to_string(),
+            email: email.to_string(),
+            password: password.to_string(),
+            two_factor_code: None,
+        }
+    }
+
+    /// Authenticator without credentials, enough to refresh or validate a stored session
+    pub fn from_base_url(base_url: &str) -> Self {
+        Self::new(base_url, "", "")
+    }
+
+    /// Code to send when the website answered with [`AuthError::TwoFactorRequired`]
+    pub fn with_two_factor_code(mut self, code: &str) -> Self {
+        self.two_factor_code = Some(code.to_string());
+        self
+    }
+
+    pub fn get_base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    pub fn get_skin_url(&self, uuid: &str) -> String {
+        format!("{}/api/skin-api/skins/{}", self.base_url, uuid)
+    }
+
+    pub fn get_cape_url(&self, uuid: &str) -> String {
+        format!("{}/api/skin-api/capes/{}", self.base_url, uuid)
+    }
+
+    pub async fn login(&self) -> Result<AzuriomUser, AuthError> {
+        let mut body = json!({
+            "email": self.email,
+            "password": self.password,
+        });
+        if let Some(code) = &self.two_factor_code {
+            body["code"] = json!(code);
+        }
+        self.post_user("authenticate", body).await
+    }
+
+    /// Check an access token and get the up-to-date user
+    pub async fn verify(&self, access_token: &str) -> Result<AzuriomUser, AuthError> {
+        self.post_user("verify", json!({ "access_token": access_token })).await
+    }
+
+    /// Invalidate the access token on the website
+    pub async fn logout(&self, access_token: &str) -> Result<(), AuthError> {
+        let response = HTTP_CLIENT
+            .post(format!("{}/api/auth/logout", self.base_url))
+            .json(&json!({ "access_token": access_token }))
+            .send()
+            .await?;
+
+        if !response.status().is_success() {
+            return Err(azuriom_error(response).await);
+        }
+        Ok(())
+    }
+
+    async fn post_user(&self, endpoint: &str, body: serde_json::Value) -> Result<AzuriomUser, AuthError> {
+        let response = HTTP_CLIENT
+            .post(format!("{}/api/auth/{}", self.base_url, endpoint))
+            .json(&body)
+            .send()
+            .await?;
+
+        if !response.status().is_success() {
+            return Err(azuriom_error(response).await);
+        }
+
+        let user: AzuriomUser = response.json().await?;
+        if user.banned {
+            return Err(AuthError::AccountBanned);
+        }
+        Ok(user)
+    }
+
+    fn to_profile(&self, user: AzuriomUser) -> UserProfile {
+        let mut profile = UserProfile::new(&user.username, &user.uuid, &user.access_token, UserType::Mojang);
+        profile.skin_url = Some(self.get_skin_url(&user.uuid));
+        profile.cape_url = Some(self.get_cape_url(&user.uuid));
+        profile
+    }
+}
+
+impl Authenticator for AzuriomAuthenticator {
+    async fn authenticate(&self) -> Result<UserProfile, AuthError> {
+        let user = self.login().await?;
+        Ok(self.to_profile(user))
+    }
+
+    async fn refresh(&self, profile: &UserProfile) -> Result<UserProfile, AuthError> {
+        // Azuriom tokens do not expire, verifying returns the same token with fresh user data
+        let user = self.verify(&profile.access_token).await?;
+        Ok(self.to_profile(user))
+    }
+
+    async fn validate(&self, profile: &UserProfile) -> Result<bool, AuthError> {
+        match self.verify(&profile.access_token).await {
+            Ok(_) => Ok(true),
+            Err(AuthError::InvalidCredentials) => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+async fn azuriom_error(response: reqwest::Response) -> AuthError {
+    let status = response.status();
+    match response.json::<AzuriomError>().await {
+        Ok(error) => match (error.status.as_str(), error.reason.as_str()) {
+            ("pending", "2fa") => AuthError::TwoFactorRequired,
+            (_, "invalid_2fa") => AuthError::InvalidTwoFactorCode,
+            (_, "invalid_credentials") | (_, "invalid_token") => AuthError::InvalidCredentials,
+            (_, "user_banned") => AuthError::AccountBanned,
+            (_, "unverified_email") | (_, "email_not_verified") => AuthError::EmailNotVerified,
+            _ => AuthError::InvalidResponse(error.message.unwrap_or(error.reason)),
+        },
+        Err(_) => AuthError::InvalidResponse(format!("HTTP {}", status)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wiremock::matchers::{body_partial_json, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn user_json() -> serde_json::Value {
+        json!({
+            "id": 1,
+            "username": "Hamadi",
+            "uuid": "37fefc81-1e26-4d31-a988-74196affc99b",
+            "access_token": "azuriom-token",
+            "email_verified": true,
+            "money": 0.0,
+            "banned": false,
+            "role": { "name": "Member", "color": "#ffffff" },
+            "created_at": "2024-01-01T00:00:00+00:00"
+        })
+    }
+
+    #[tokio::test]
+    async fn two_factor_challenge_then_login() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/api/auth/authenticate"))
+            .and(body_partial_json(json!({ "code": "123456" })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(user_json()))
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/api/auth/authenticate"))
+            .respond_with(ResponseTemplate::new(422).set_body_json(json!({
+                "status": "pending",
+                "reason": "2fa",
+                "message": "Two factor authentication is required"
+            })))
+            .mount(&server)
+            .await;
+
+        let authenticator = AzuriomAuthenticator::new(&server.uri(), "hamadi@example.com", "password");
+        let error = authenticator.authenticate().await.unwrap_err();
+        assert!(matches!(error, AuthError::TwoFactorRequired));
+
+        let profile = authenticator.with_two_factor_code("123456").authenticate().await.unwrap();
+        assert_eq!(profile.name, "Hamadi");
+        assert_eq!(profile.access_token, "azuriom-token");
+        assert_eq!(
+            profile.skin_url.as_deref(),
+            Some(format!("{}/api/skin-api/skins/37fefc81-1e26-4d31-a988-74196affc99b", server.uri()).as_str())
+        );
+    }
+
+    #[tokio::test]
+    async fn banned_accounts_are_rejected() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/api/auth/authenticate"))
+            .respond_with(ResponseTemplate::new(403).set_body_json(json!({
+                "status": "error",
+                "reason": "user_banned",
+                "message": "User banned"
+            })))
+            .mount(&server)
+            .await;
+
+        let authenticator = AzuriomAuthenticator::new(&server.uri(), "hamadi@example.com", "password");
+        let error = authenticator.authenticate().await.unwrap_err();
+        assert!(matches!(error, AuthError::AccountBanned));
+    }
+}
@@ -0,0 +1,108 @@
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+
+/// Errors returned by the authentication backends
+#[derive(Debug)]
+pub enum AuthError {
+    /// Transport error while talking to an authentication server
+    Http(reqwest::Error),
+    /// Local I/O error, e.g. the loopback listener could not be bound
+    Io(std::io::Error),
+    /// The server answered with something we could not understand
+    InvalidResponse(String),
+    /// The user did not enter the device code before it expired
+    DeviceCodeExpired,
+    /// The user refused to grant access to the launcher
+    AuthorizationDeclined,
+    /// The browser login was not completed in time
+    LoginTimeout,
+    /// The browser login was cancelled by the launcher
+    LoginCancelled,
+    /// The `state` returned by the redirect does not match the one we sent
+    StateMismatch,
+    /// The Microsoft account has no Xbox profile yet (XErr 2148916233)
+    XboxNoAccount,
+    /// Xbox Live is not available in the account's country (XErr 2148916235)
+    XboxCountryUnavailable,
+    /// The account needs adult verification, South Korea only (XErr 2148916236 / 2148916237)
+    XboxAdultVerificationRequired,
+    /// The account is a child account and must be added to a family (XErr 2148916238)
+    XboxChildAccount,
+    /// Any other XSTS error code
+    Xbox(u64),
+    /// The account is authenticated but has no Minecraft profile
+    NoMinecraftProfile,
+    /// The username does not follow Minecraft's rules
+    InvalidUsername(String),
+    /// The profile has no refresh token, the user must log in again
+    RefreshTokenMissing,
+    /// Wrong email/username or password, or an access token that is no longer valid
+    InvalidCredentials,
+    /// The account has two-factor authentication, the code must be sent with the credentials
+    TwoFactorRequired,
+    /// The two-factor code is wrong
+    InvalidTwoFactorCode,
+    /// The account is banned from the website
+    AccountBanned,
+    /// The account email address must be verified before logging in
+    EmailNotVerified,
+    /// The session could not be refreshed, the user must log in again
+    LoginRequired,
+    /// The skin file is not a PNG the game accepts
+    InvalidSkin(String),
+    /// The account store file has a layout this launcher does not know, it is left untouched
+    Store(String),
+}
+
+impl Display for AuthError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AuthError::Http(e) => write!(f, "HTTP error: {}", e),
+            AuthError::Io(e) => write!(f, "I/O error: {}", e),
+            AuthError::InvalidResponse(message) => write!(f, "Invalid response from authentication server: {}", message),
+            AuthError::DeviceCodeExpired => f.write_str("The device code has expired, please try again"),
+            AuthError::AuthorizationDeclined => f.write_str("The authorization request was declined"),
+            AuthError::LoginTimeout => f.write_str("The login was not completed in time"),
+            AuthError::LoginCancelled => f.write_str("The login was cancelled"),
+            AuthError::StateMismatch => f.write_str("The login response does not match the request, please try again"),
+            AuthError::XboxNoAccount => f.write_str("This Microsoft account does not have an Xbox account, please create one on xbox.com"),
+            AuthError::XboxCountryUnavailable => f.write_str("Xbox Live is not available in your country"),
+            AuthError::XboxAdultVerificationRequired => f.write_str("This account needs adult verification on xbox.com"),
+            AuthError::XboxChildAccount => f.write_str("This is a child account, it must be added to a family by an adult"),
+            AuthError::Xbox(code) => write!(f, "Xbox Live authentication failed (XErr {})", code),
+            AuthError::NoMinecraftProfile => f.write_str("This account does not have a Minecraft profile"),
+            AuthError::InvalidUsername(name) => write!(f, "'{}' is not a valid username: use 3 to 16 letters, digits or underscores", name),
+            AuthError::RefreshTokenMissing => f.write_str("No refresh token available, please log in again"),
+            AuthError::InvalidCredentials => f.write_str("Invalid credentials"),
+            AuthError::TwoFactorRequired => f.write_str("A two-factor authentication code is required"),
+            AuthError::InvalidTwoFactorCode => f.write_str("Invalid two-factor authentication code"),
+            AuthError::AccountBanned => f.write_str("This account is banned"),
+            AuthError::EmailNotVerified => f.write_str("The email address of this account is not verified"),
+            AuthError::LoginRequired => f.write_str("The session has expired, please log in again"),
+            AuthError::InvalidSkin(reason) => write!(f, "Invalid skin: {}", reason),
+            AuthError::Store(reason) => write!(f, "Could not read the account store: {}", reason),
+        }
+    }
+}
+
+impl Error for AuthError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            AuthError::Http(e) => Some(e),
+            AuthError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<reqwest::Error> for AuthError {
+    fn from(e: reqwest::Error) -> Self {
+        AuthError::Http(e)
+    }
+}
+
+impl From<std::io::Error> for AuthError {
+    fn from(e: std::io::Error) -> Self {
+        AuthError::Io(e)
+    }
+}
@@ -0,0 +1,605 @@
+use std::time::{Duration, Instant};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use tracing::{debug, info};
+use crate::minecraft::auth::authenticator::Authenticator;
+use crate::minecraft::auth::error::AuthError;
+use crate::minecraft::auth::offline::offline_uuid;
+use crate::minecraft::auth::profile::{unix_time, GameOwnership, UserProfile, UserType};
+use crate::minecraft::auth::skins::MinecraftProfile;
+use crate::utils::hosts::HTTP_CLIENT;
+
+pub(crate) const MICROSOFT_SCOPE: &str = "XboxLive.signin offline_access";
+/// Name used for accounts without a Minecraft profile, which can only play the demo
+const DEMO_PLAYER_NAME: &str = "Player";
+
+/// Endpoints used by the Microsoft -> Xbox Live -> XSTS -> Minecraft chain.
+///
+/// Every URL can be overridden, which allows the whole chain to run against a local server.
+#[derive(Debug, Clone)]
+pub struct MicrosoftEndpoints {
+    pub authorize_url: String,
+    pub device_code_url: String,
+    pub token_url: String,
+    pub xbox_live_url: String,
+    pub xsts_url: String,
+    pub minecraft_login_url: String,
+    pub minecraft_profile_url: String,
+    pub entitlements_url: String,
+}
+
+impl Default for MicrosoftEndpoints {
+    fn default() -> Self {
+        Self {
+            authorize_url: "https://login.microsoftonline.com/consumers/oauth2/v2.0/authorize".to_string(),
+            device_code_url: "https://login.microsoftonline.com/consumers/oauth2/v2.0/devicecode".to_string(),
+            token_url: "https://login.microsoftonline.com/consumers/oauth2/v2.0/token".to_string(),
+            xbox_live_url: "https://user.auth.xboxlive.com/user/authenticate".to_string(),
+            xsts_url: "https://xsts.auth.xboxlive.com/xsts/authorize".to_string(),
+            minecraft_login_url: "https://api.minecraftservices.com/authentication/login_with_xbox".to_string(),
+            minecraft_profile_url: "https://api.minecraftservices.com/minecraft/profile".to_string(),
+            entitlements_url: "https://api.minecraftservices.com/entitlements/mcstore".to_string(),
+        }
+    }
+}
+
+impl MicrosoftEndpoints {
+    /// Point every endpoint to the same base URL, keeping the official paths
+    pub fn with_base_url(base_url: &str) -> Self {
+        let base_url = base_url.trim_end_matches('/');
+        Self {
+            authorize_url: format!("{}/consumers/oauth2/v2.0/authorize", base_url),
+            device_code_url: format!("{}/consumers/oauth2/v2.0/devicecode", base_url),
+            token_url: format!("{}/consumers/oauth2/v2.0/token", base_url),
+            xbox_live_url: format!("{}/user/authenticate", base_url),
+            xsts_url: format!("{}/xsts/authorize", base_url),
+            minecraft_login_url: format!("{}/authentication/login_with_xbox", base_url),
+            minecraft_profile_url: format!("{}/minecraft/profile", base_url),
+            entitlements_url: format!("{}/entitlements/mcstore", base_url),
+        }
+    }
+}
+
+/// Code the user has to enter on the verification page
+#[derive(Debug, Clone, Deserialize)]
+pub struct DeviceCode {
+    pub device_code: String,
+    pub user_code: String,
+    pub verification_uri: String,
+    pub expires_in: u64,
+    pub interval: u64,
+    pub message: Option<String>,
+}
+
+/// OAuth token returned by the Microsoft identity platform
+#[derive(Debug, Clone, Deserialize)]
+pub struct MicrosoftToken {
+    pub access_token: String,
+    pub refresh_token: Option<String>,
+    pub expires_in: u64,
+}
+
+#[derive(Debug, Deserialize)]
+struct OAuthError {
+    error: String,
+    error_description: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct XboxResponse {
+    token: String,
+    display_claims: XboxDisplayClaims,
+}
+
+#[derive(Debug, Deserialize)]
+struct XboxDisplayClaims {
+    xui: Vec<XboxUserInfo>,
+}
+
+#[derive(Debug, Deserialize)]
+struct XboxUserInfo {
+    uhs: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct XstsError {
+    #[serde(rename = "XErr")]
+    xerr: u64,
+}
+
+#[derive(Debug, Deserialize)]
+struct MinecraftLoginResponse {
+    access_token: String,
+    expires_in: u64,
+}
+
+#[derive(Debug, Deserialize)]
+struct EntitlementsResponse {
+    #[serde(default)]
+    items: Vec<Entitlement>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Entitlement {
+    name: String,
+    source: Option<String>,
+}
+
+
+type DeviceCodeHandler = Box<dyn Fn(&DeviceCode) + Send + Sync>;
+
+pub struct MicrosoftAuthenticator {
+    client_id: String,
+    endpoints: MicrosoftEndpoints,
+    on_device_code: DeviceCodeHandler,
+}
+
+impl MicrosoftAuthenticator {
+    /// `client_id` is the Azure application ID of the launcher
+    pub fn new(client_id: &str) -> Self {
+        Self {
+            client_id: client_id.to_string(),
+            endpoints: MicrosoftEndpoints::default(),
+            on_device_code: Box::new(|code| info!("To sign in, open {} and enter the code {}", code.verification_uri, code.user_code)),
+        }
+    }
+
+    pub fn with_endpoints(mut self, endpoints: MicrosoftEndpoints) -> Self {
+        self.endpoints = endpoints;
+        self
+    }
+
+    /// Called with the device code to show when [`Authenticator::authenticate`] is used
+    pub fn with_device_code_handler<F>(mut self, on_device_code: F) -> Self
+    where
+        F: Fn(&DeviceCode) + Send + Sync + 'static,
+    {
+        self.on_device_code = Box::new(on_device_code);
+        self
+    }
+
+    pub fn get_client_id(&self) -> &str {
+        &self.client_id
+    }
+
+    pub fn get_endpoints(&self) -> &MicrosoftEndpoints {
+        &self.endpoints
+    }
+
+    /// Run the complete device-code flow.
+    ///
+    /// `on_device_code` is called once with the code that must be shown to the user.
+    pub async fn authenticate_device_code<F>(&self, on_device_code: F) -> Result<UserProfile, AuthError>
+    where
+        F: Fn(&DeviceCode),
+    {
+        let device_code = self.request_device_code().await?;
+        on_device_code(&device_code);
+        let token = self.poll_device_code(&device_code).await?;
+        self.login_with_microsoft_token(&token).await
+    }
+
+    pub async fn request_device_code(&self) -> Result<DeviceCode, AuthError> {
+        let response = HTTP_CLIENT
+            .post(&self.endpoints.device_code_url)
+            .form(&[("client_id", self.client_id.as_str()), ("scope", MICROSOFT_SCOPE)])
+            .send()
+            .await?;
+
+        if !response.status().is_success() {
+            return Err(oauth_error(response).await);
+        }
+        Ok(response.json().await?)
+    }
+
+    /// Poll the token endpoint until the user has entered the device code
+    pub async fn poll_device_code(&self, device_code: &DeviceCode) -> Result<MicrosoftToken, AuthError> {
+        // A zero interval would poll the endpoint in a busy loop
+        let mut interval = device_code.interval.max(1);
+        let deadline = Instant::now() + Duration::from_secs(device_code.expires_in);
+
+        loop {
+            let response = HTTP_CLIENT
+                .post(&self.endpoints.token_url)
+                .form(&[
+                    ("grant_type", "urn:ietf:params:oauth:grant-type:device_code"),
+                    ("client_id", self.client_id.as_str()),
+                    ("device_code", device_code.device_code.as_str()),
+                ])
+                .send()
+                .await?;
+
+            if response.status().is_success() {
+                return Ok(response.json().await?);
+            }
+
+            let error: OAuthError = response
+                .json()
+                .await
+                .map_err(|e| AuthError::InvalidResponse(e.to_string()))?;
+
+            match error.error.as_str() {
+                "authorization_pending" => {}
+                // The server asks to wait 5 more seconds between each request
+                "slow_down" => interval += 5,
+                "authorization_declined" => return Err(AuthError::AuthorizationDeclined),
+                "expired_token" | "bad_verification_code" => return Err(AuthError::DeviceCodeExpired),
+                _ => return Err(AuthError::InvalidResponse(error.error_description.unwrap_or(error.error))),
+            }
+
+            if Instant::now() + Duration::from_secs(interval) > deadline {
+                return Err(AuthError::DeviceCodeExpired);
+            }
+            tokio::time::sleep(Duration::from_secs(interval)).await;
+        }
+    }
+
+    /// Get a new Microsoft token from a refresh token
+    pub async fn refresh_microsoft_token(&self, refresh_token: &str) -> Result<MicrosoftToken, AuthError> {
+        let response = HTTP_CLIENT
+            .post(&self.endpoints.token_url)
+            .form(&[
+                ("grant_type", "refresh_token"),
+                ("client_id", self.client_id.as_str()),
+                ("refresh_token", refresh_token),
+                ("scope", MICROSOFT_SCOPE),
+            ])
+            .send()
+            .await?;
+
+        if !response.status().is_success() {
+            return Err(oauth_error(response).await);
+        }
+        Ok(response.json().await?)
+    }
+
+    /// Exchange a Microsoft token for a Minecraft profile through Xbox Live and XSTS
+    pub async fn login_with_microsoft_token(&self, token: &MicrosoftToken) -> Result<UserProfile, AuthError> {
+        let xbox_live = self.authenticate_xbox_live(&token.access_token).await?;
+        let xsts = self.authorize_xsts(&xbox_live.token).await?;
+
+        let user_hash = xsts
+            .display_claims
+            .xui
+            .first()
+            .map(|xui| xui.uhs.clone())
+            .ok_or_else(|| AuthError::InvalidResponse("XSTS response has no user hash".to_string()))?;
+
+        let minecraft = self.login_minecraft(&user_hash, &xsts.token).await?;
+        let ownership = self.get_ownership(&minecraft.access_token).await?;
+
+        let mut user = match self.get_minecraft_profile(&minecraft.access_token).await {
+            Ok(profile) => {
+                debug!("Logged in as {} ({})", profile.name, profile.id);
+                let mut user = UserProfile::new(&profile.name, &profile.id, &minecraft.access_token, UserType::Msa);
+                profile.update_user_profile(&mut user);
+                user
+            }
+            // Accounts that do not own the game have no profile, they can still play the demo
+            Err(AuthError::NoMinecraftProfile) if ownership == GameOwnership::NotOwned => {
+                debug!("Logged in without a Minecraft profile, only the demo is available");
+                UserProfile::new(DEMO_PLAYER_NAME, &offline_uuid(&user_hash), &minecraft.access_token, UserType::Msa)
+            }
+            Err(e) => return Err(e),
+        };
+        user.refresh_token = token.refresh_token.clone();
+        user.expires_at = Some(unix_time() + minecraft.expires_in);
+        user.ownership = Some(ownership);
+        Ok(user)
+    }
+
+    /// Check whether the account owns Java Edition, directly or through Game Pass
+    pub async fn get_ownership(&self, access_token: &str) -> Result<GameOwnership, AuthError> {
+        let response: EntitlementsResponse = HTTP_CLIENT
+            .get(&self.endpoints.entitlements_url)
+            .bearer_auth(access_token)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        let game_pass = response.items.iter().any(|item| {
+            item.name.starts_with("product_game_pass") || item.source.as_deref() == Some("GAMEPASS")
+        });
+        let owns_game = response
+            .items
+            .iter()
+            .any(|item| item.name == "product_minecraft" || item.name == "game_minecraft");
+
+        Ok(match (owns_game, game_pass) {
+            // Game Pass subscribers who also bought the game own it
+            (true, _) => GameOwnership::Owned,
+            (false, true) => GameOwnership::GamePass,
+            (false, false) => GameOwnership::NotOwned,
+        })
+    }
+
+    async fn authenticate_xbox_live(&self, microsoft_token: &str) -> Result<XboxResponse, AuthError> {
+        let body = json!({
+            "Properties": {
+                "AuthMethod": "RPS",
+                "SiteName": "user.auth.xboxlive.com",
+                "RpsTicket": format!("d={}", microsoft_token),
+            },
+            "RelyingParty": "http://auth.xboxlive.com",
+            "TokenType": "JWT",
+        });
+
+        let response = HTTP_CLIENT
+            .post(&self.endpoints.xbox_live_url)
+            .json(&body)
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(response.json().await?)
+    }
+
+    async fn authorize_xsts(&self, xbox_live_token: &str) -> Result<XboxResponse, AuthError> {
+        let body = json!({
+            "Properties": {
+                "SandboxId": "RETAIL",
+                "UserTokens": [xbox_live_token],
+            },
+            "RelyingParty": "rp://api.minecraftservices.com/",
+            "TokenType": "JWT",
+        });
+
+        let response = HTTP_CLIENT.post(&self.endpoints.xsts_url).json(&body).send().await?;
+
+        if response.status() == reqwest::StatusCode::UNAUTHORIZED {
+            let error: XstsError = response
+                .json()
+                .await
+                .map_err(|e| AuthError::InvalidResponse(e.to_string()))?;
+            return Err(xsts_error(error.xerr));
+        }
+        Ok(response.error_for_status()?.json().await?)
+    }
+
+    async fn login_minecraft(&self, user_hash: &str, xsts_token: &str) -> Result<MinecraftLoginResponse, AuthError> {
+        let body = json!({
+            "identityToken": format!("XBL3.0 x={};{}", user_hash, xsts_token),
+        });
+
+        let response = HTTP_CLIENT
+            .post(&self.endpoints.minecraft_login_url)
+            .json(&body)
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(response.json().await?)
+    }
+
+    async fn get_minecraft_profile(&self, access_token: &str) -> Result<MinecraftProfile, AuthError> {
+        let response = HTTP_CLIENT
+            .get(&self.endpoints.minecraft_profile_url)
+            .bearer_auth(access_token)
+            .send()
+            .await?;
+
+        // Accounts that never bought the game have no profile
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            return Err(AuthError::NoMinecraftProfile);
+        }
+        Ok(response.error_for_status()?.json().await?)
+    }
+}
+
+impl Authenticator for MicrosoftAuthenticator {
+    async fn authenticate(&self) -> Result<UserProfile, AuthError> {
+        self.authenticate_device_code(&self.on_device_code).await
+    }
+
+    async fn refresh(&self, profile: &UserProfile) -> Result<UserProfile, AuthError> {
+        let refresh_token = profile.refresh_token.as_deref().ok_or(AuthError::RefreshTokenMissing)?;
+        let token = self.refresh_microsoft_token(refresh_token).await?;
+        self.login_with_microsoft_token(&token).await
+    }
+
+    async fn validate(&self, profile: &UserProfile) -> Result<bool, AuthError> {
+        let response = HTTP_CLIENT
+            .get(&self.endpoints.minecraft_profile_url)
+            .bearer_auth(&profile.access_token)
+            .send()
+            .await?;
+        match response.status() {
+            status if status.is_success() => Ok(true),
+            reqwest::StatusCode::UNAUTHORIZED | reqwest::StatusCode::FORBIDDEN => Ok(false),
+            status => Err(AuthError::InvalidResponse(format!("HTTP {}", status))),
+        }
+    }
+}
+
+fn xsts_error(xerr: u64) -> AuthError {
+    match xerr {
+        2148916233 => AuthError::XboxNoAccount,
+        2148916235 => AuthError::XboxCountryUnavailable,
+        2148916236 | 2148916237 => AuthError::XboxAdultVerificationRequired,
+        2148916238 => AuthError::XboxChildAccount,
+        code => AuthError::Xbox(code),
+    }
+}
+
+pub(crate) async fn oauth_error(response: reqwest::Response) -> AuthError {
+    let status = response.status();
+    match response.json::<Value>().await {
+        Ok(body) => match body["error"].as_str() {
+            Some("authorization_declined") => AuthError::AuthorizationDeclined,
+            Some("expired_token") => AuthError::DeviceCodeExpired,
+            // The refresh token was revoked or has expired
+            Some("invalid_grant") => AuthError::InvalidCredentials,
+            _ => AuthError::InvalidResponse(format!("HTTP {}: {}", status, body)),
+        },
+        Err(_) => AuthError::InvalidResponse(format!("HTTP {}", status)),
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use wiremock::matchers::{body_string_contains, header, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    pub(crate) async fn mount_xbox_chain(server: &MockServer, xsts: ResponseTemplate) {
+        Mock::given(method("POST"))
+            .and(path("/user/authenticate"))
+            .and(body_string_contains("d=ms-access"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "Token": "xbl-token",
+                "DisplayClaims": { "xui": [{ "uhs": "user-hash" }] }
+            })))
+            .mount(server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/xsts/authorize"))
+            .and(body_string_contains("xbl-token"))
+            .respond_with(xsts)
+            .mount(server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/authentication/login_with_xbox"))
+            .and(body_string_contains("XBL3.0 x=user-hash;xsts-token"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "access_token": "mc-token",
+                "expires_in": 86400
+            })))
+            .mount(server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/entitlements/mcstore"))
+            .and(header("authorization", "Bearer mc-token"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "items": [
+                    { "name": "product_minecraft", "signature": "sig", "source": "PURCHASE" },
+                    { "name": "game_minecraft", "signature": "sig", "source": "PURCHASE" }
+                ],
+                "signature": "sig",
+                "keyId": "1"
+            })))
+            .mount(server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/minecraft/profile"))
+            .and(header("authorization", "Bearer mc-token"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "id": "069a79f444e94726a5befca90e38aaf5",
+                "name": "Notch"
+            })))
+            .mount(server)
+            .await;
+    }
+
+    pub(crate) fn xsts_success() -> ResponseTemplate {
+        ResponseTemplate::new(200).set_body_json(json!({
+            "Token": "xsts-token",
+            "DisplayClaims": { "xui": [{ "uhs": "user-hash" }] }
+        }))
+    }
+
+    #[tokio::test]
+    async fn device_code_flow_returns_profile() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/consumers/oauth2/v2.0/devicecode"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "device_code": "device",
+                "user_code": "ABCD-EFGH",
+                "verification_uri": "https://microsoft.com/link",
+                "expires_in": 900,
+                "interval": 0
+            })))
+            .mount(&server)
+            .await;
+        // First poll is pending, the second one succeeds
+        Mock::given(method("POST"))
+            .and(path("/consumers/oauth2/v2.0/token"))
+            .respond_with(ResponseTemplate::new(400).set_body_json(json!({ "error": "authorization_pending" })))
+            .up_to_n_times(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/consumers/oauth2/v2.0/token"))
+            .and(body_string_contains("device_code=device"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "access_token": "ms-access",
+                "refresh_token": "ms-refresh",
+                "expires_in": 3600
+            })))
+            .mount(&server)
+            .await;
+        mount_xbox_chain(&server, xsts_success()).await;
+
+        let authenticator = MicrosoftAuthenticator::new("client")
+            .with_endpoints(MicrosoftEndpoints::with_base_url(&server.uri()));
+        let profile = authenticator
+            .authenticate_device_code(|code| assert_eq!(code.user_code, "ABCD-EFGH"))
+            .await
+            .unwrap();
+
+        assert_eq!(profile.name, "Notch");
+        assert_eq!(profile.uuid, "069a79f444e94726a5befca90e38aaf5");
+        assert_eq!(profile.access_token, "mc-token");
+        assert_eq!(profile.refresh_token.as_deref(), Some("ms-refresh"));
+        assert_eq!(profile.ownership, Some(GameOwnership::Owned));
+    }
+
+    #[tokio::test]
+    async fn owners_with_game_pass_are_owners() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/entitlements/mcstore"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "items": [
+                { "name": "product_minecraft", "signature": "" },
+                { "name": "product_game_pass_ultimate", "source": "GAMEPASS", "signature": "" }
+            ] })))
+            .mount(&server)
+            .await;
+
+        let authenticator = MicrosoftAuthenticator::new("client")
+            .with_endpoints(MicrosoftEndpoints::with_base_url(&server.uri()));
+        assert_eq!(authenticator.get_ownership("mc-token").await.unwrap(), GameOwnership::Owned);
+    }
+
+    #[tokio::test]
+    async fn accounts_without_the_game_get_a_demo_profile() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/entitlements/mcstore"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "items": [] })))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/minecraft/profile"))
+            .respond_with(ResponseTemplate::new(404).set_body_json(json!({ "error": "NOT_FOUND" })))
+            .mount(&server)
+            .await;
+        mount_xbox_chain(&server, xsts_success()).await;
+
+        let authenticator = MicrosoftAuthenticator::new("client")
+            .with_endpoints(MicrosoftEndpoints::with_base_url(&server.uri()));
+        let token = MicrosoftToken { access_token: "ms-access".to_string(), refresh_token: None, expires_in: 3600 };
+        let profile = authenticator.login_with_microsoft_token(&token).await.unwrap();
+
+        assert_eq!(profile.ownership, Some(GameOwnership::NotOwned));
+        assert!(profile.is_demo());
+    }
+
+    #[tokio::test]
+    async fn xsts_errors_are_mapped() {
+        let server = MockServer::start().await;
+        mount_xbox_chain(
+            &server,
+            ResponseTemplate::new(401).set_body_json(json!({ "Identity": "0", "XErr": 2148916238u64 })),
+        )
+        .await;
+
+        let authenticator = MicrosoftAuthenticator::new("client")
+            .with_endpoints(MicrosoftEndpoints::with_base_url(&server.uri()));
+        let token = MicrosoftToken { access_token: "ms-access".to_string(), refresh_token: None, expires_in: 3600 };
+        let error = authenticator.login_with_microsoft_token(&token).await.unwrap_err();
+
+        assert!(matches!(error, AuthError::XboxChildAccount));
+    }
+}
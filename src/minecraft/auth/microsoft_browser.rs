@@ -0,0 +1,296 @@
+use std::time::Duration;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use reqwest::Url;
+use sha2::{Digest, Sha256};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::oneshot::Receiver;
+use crate::minecraft::auth::error::AuthError;
+use crate::minecraft::auth::microsoft::{oauth_error, MicrosoftAuthenticator, MicrosoftToken, MICROSOFT_SCOPE};
+use crate::minecraft::auth::profile::UserProfile;
+use crate::utils::hosts::HTTP_CLIENT;
+
+const SUCCESS_PAGE: &str = "<html><body><h1>Login successful</h1><p>You can close this window and go back to the launcher.</p></body></html>";
+const FAILURE_PAGE: &str = "<html><body><h1>Login failed</h1><p>Please go back to the launcher and try again.</p></body></html>";
+
+/// Authorization-code login in progress.
+///
+/// The frontend opens [`BrowserLogin::get_authorize_url`] in the user's browser, then
+/// [`BrowserLogin::wait_for_code`] catches the redirect on the loopback listener.
+pub struct BrowserLogin {
+    listener: TcpListener,
+    redirect_uri: String,
+    authorize_url: String,
+    state: String,
+    code_verifier: String,
+}
+
+impl BrowserLogin {
+    pub fn get_authorize_url(&self) -> &str {
+        &self.authorize_url
+    }
+
+    pub fn get_redirect_uri(&self) -> &str {
+        &self.redirect_uri
+    }
+
+    pub fn get_code_verifier(&self) -> &str {
+        &self.code_verifier
+    }
+
+    /// Wait for the browser to be redirected to the listener and return the authorization code.
+    ///
+    /// Fails with [`AuthError::LoginTimeout`] after `timeout`, or with [`AuthError::LoginCancelled`]
+    /// as soon as `cancel` fires or its sender is dropped.
+    pub async fn wait_for_code(&self, timeout: Duration, cancel: Receiver<()>) -> Result<String, AuthError> {
+        tokio::select! {
+            result = self.accept_redirect() => result,
+            _ = tokio::time::sleep(timeout) => Err(AuthError::LoginTimeout),
+            _ = cancel => Err(AuthError::LoginCancelled),
+        }
+    }
+
+    async fn accept_redirect(&self) -> Result<String, AuthError> {
+        loop {
+            let (mut stream, _) = self.listener.accept().await?;
+            let Some(target) = read_request_target(&mut stream).await? else {
+                continue;
+            };
+
+            let url = Url::parse(&format!("http://127.0.0.1{}", target))
+                .map_err(|e| AuthError::InvalidResponse(e.to_string()))?;
+
+            // Browsers also ask for /favicon.ico, only the root path carries the answer
+            if url.path() != "/" {
+                write_response(&mut stream, "404 Not Found", "").await?;
+                continue;
+            }
+
+            let mut code = None;
+            let mut state = None;
+            let mut error = None;
+            for (key, value) in url.query_pairs() {
+                match key.as_ref() {
+                    "code" => code = Some(value.into_owned()),
+                    "state" => state = Some(value.into_owned()),
+                    "error" => error = Some(value.into_owned()),
+                    _ => {}
+                }
+            }
+
+            if state.as_deref() != Some(self.state.as_str()) {
+                write_response(&mut stream, "400 Bad Request", FAILURE_PAGE).await?;
+                return Err(AuthError::StateMismatch);
+            }
+
+            if let Some(error) = error {
+                write_response(&mut stream, "200 OK", FAILURE_PAGE).await?;
+                return Err(match error.as_str() {
+                    "access_denied" => AuthError::AuthorizationDeclined,
+                    _ => AuthError::InvalidResponse(error),
+                });
+            }
+
+            return match code {
+                Some(code) => {
+                    write_response(&mut stream, "200 OK", SUCCESS_PAGE).await?;
+                    Ok(code)
+                }
+                None => {
+                    write_response(&mut stream, "400 Bad Request", FAILURE_PAGE).await?;
+                    Err(AuthError::InvalidResponse("Redirect has no authorization code".to_string()))
+                }
+            };
+        }
+    }
+}
+
+impl MicrosoftAuthenticator {
+    /// Bind a temporary listener on `127.0.0.1` and prepare the PKCE authorize URL
+    pub async fn start_browser_login(&self) -> Result<BrowserLogin, AuthError> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let redirect_uri = format!("http://127.0.0.1:{}", listener.local_addr()?.port());
+
+        let code_verifier = random_token::<32>();
+        let state = random_token::<16>();
+        let code_challenge = URL_SAFE_NO_PAD.encode(Sha256::digest(code_verifier.as_bytes()));
+
+        let authorize_url = Url::parse_with_params(
+            &self.get_endpoints().authorize_url,
+            &[
+                ("client_id", self.get_client_id()),
+                ("response_type", "code"),
+                ("redirect_uri", redirect_uri.as_str()),
+                ("response_mode", "query"),
+                ("scope", MICROSOFT_SCOPE),
+                ("state", state.as_str()),
+                ("code_challenge", code_challenge.as_str()),
+                ("code_challenge_method", "S256"),
+                ("prompt", "select_account"),
+            ],
+        )
+        .map_err(|e| AuthError::InvalidResponse(e.to_string()))?
+        .to_string();
+
+        Ok(BrowserLogin { listener, redirect_uri, authorize_url, state, code_verifier })
+    }
+
+    /// Exchange the authorization code, proving possession of the PKCE verifier
+    pub async fn exchange_authorization_code(&self, code: &str, login: &BrowserLogin) -> Result<MicrosoftToken, AuthError> {
+        let response = HTTP_CLIENT
+            .post(&self.get_endpoints().token_url)
+            .form(&[
+                ("grant_type", "authorization_code"),
+                ("client_id", self.get_client_id()),
+                ("code", code),
+                ("redirect_uri", login.get_redirect_uri()),
+                ("code_verifier", login.get_code_verifier()),
+                ("scope", MICROSOFT_SCOPE),
+            ])
+            .send()
+            .await?;
+
+        if !response.status().is_success() {
+            return Err(oauth_error(response).await);
+        }
+        Ok(response.json().await?)
+    }
+
+    /// Run the complete browser flow.
+    ///
+    /// `on_authorize_url` is called once with the URL the frontend must open.
+    pub async fn authenticate_browser<F>(&self, on_authorize_url: F, timeout: Duration, cancel: Receiver<()>) -> Result<UserProfile, AuthError>
+    where
+        F: Fn(&str),
+    {
+        let login = self.start_browser_login().await?;
+        on_authorize_url(login.get_authorize_url());
+        let code = login.wait_for_code(timeout, cancel).await?;
+        let token = self.exchange_authorization_code(&code, &login).await?;
+        self.login_with_microsoft_token(&token).await
+    }
+}
+
+fn random_token<const N: usize>() -> String {
+    URL_SAFE_NO_PAD.encode(rand::random::<[u8; N]>())
+}
+
+/// Read the request head and return the request target of a GET request
+async fn read_request_target(stream: &mut TcpStream) -> Result<Option<String>, AuthError> {
+    let mut head = Vec::new();
+    let mut buf = [0u8; 1024];
+
+    while !head.windows(4).any(|w| w == b"\r\n\r\n") {
+        let read = stream.read(&mut buf).await?;
+        if read == 0 || head.len() > 16 * 1024 {
+            break;
+        }
+        head.extend_from_slice(&buf[..read]);
+    }
+
+    let head = String::from_utf8_lossy(&head);
+    let mut parts = head.lines().next().unwrap_or_default().split_whitespace();
+    match (parts.next(), parts.next()) {
+        (Some("GET"), Some(target)) => Ok(Some(target.to_string())),
+        _ => Ok(None),
+    }
+}
+
+async fn write_response(stream: &mut TcpStream, status: &str, body: &str) -> Result<(), AuthError> {
+    let response = format!(
+        "HTTP/1.1 {}\r\nContent-Type: text/html; charset=utf-8\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    );
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::minecraft::auth::microsoft::MicrosoftEndpoints;
+    use std::collections::HashMap;
+    use tokio::sync::oneshot;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, Request, ResponseTemplate};
+
+    fn query(url: &str) -> HashMap<String, String> {
+        Url::parse(url).unwrap().query_pairs().into_owned().collect()
+    }
+
+    #[tokio::test]
+    async fn redirect_is_exchanged_with_pkce() {
+        let server = MockServer::start().await;
+        let authenticator = MicrosoftAuthenticator::new("client")
+            .with_endpoints(MicrosoftEndpoints::with_base_url(&server.uri()));
+        let login = authenticator.start_browser_login().await.unwrap();
+        let params = query(login.get_authorize_url());
+        let challenge = params["code_challenge"].clone();
+
+        // Stand-in token endpoint checking that the verifier matches the challenge
+        Mock::given(method("POST"))
+            .and(path("/consumers/oauth2/v2.0/token"))
+            .respond_with(move |request: &Request| {
+                let form: HashMap<String, String> = Url::parse(&format!("http://localhost/?{}", String::from_utf8_lossy(&request.body)))
+                    .unwrap()
+                    .query_pairs()
+                    .into_owned()
+                    .collect();
+                let verifier_challenge = URL_SAFE_NO_PAD.encode(Sha256::digest(form["code_verifier"].as_bytes()));
+                if form["code"] == "auth-code" && verifier_challenge == challenge {
+                    ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                        "access_token": "ms-access",
+                        "refresh_token": "ms-refresh",
+                        "expires_in": 3600
+                    }))
+                } else {
+                    ResponseTemplate::new(400).set_body_json(serde_json::json!({ "error": "invalid_grant" }))
+                }
+            })
+            .mount(&server)
+            .await;
+
+        let redirect = format!("{}/?code=auth-code&state={}", login.get_redirect_uri(), params["state"]);
+        let browser = tokio::spawn(async move { HTTP_CLIENT.get(redirect).send().await.unwrap().status() });
+
+        let (_cancel_tx, cancel_rx) = oneshot::channel();
+        let code = login.wait_for_code(Duration::from_secs(10), cancel_rx).await.unwrap();
+        assert_eq!(code, "auth-code");
+        assert!(browser.await.unwrap().is_success());
+
+        let token = authenticator.exchange_authorization_code(&code, &login).await.unwrap();
+        assert_eq!(token.access_token, "ms-access");
+    }
+
+    #[tokio::test]
+    async fn wrong_state_is_rejected() {
+        let authenticator = MicrosoftAuthenticator::new("client");
+        let login = authenticator.start_browser_login().await.unwrap();
+
+        let redirect = format!("{}/?code=auth-code&state=forged", login.get_redirect_uri());
+        tokio::spawn(async move { HTTP_CLIENT.get(redirect).send().await });
+
+        let (_cancel_tx, cancel_rx) = oneshot::channel();
+        let error = login.wait_for_code(Duration::from_secs(10), cancel_rx).await.unwrap_err();
+        assert!(matches!(error, AuthError::StateMismatch));
+    }
+
+    #[tokio::test]
+    async fn listener_can_be_cancelled_or_time_out() {
+        let authenticator = MicrosoftAuthenticator::new("client");
+        let login = authenticator.start_browser_login().await.unwrap();
+
+        let (cancel_tx, cancel_rx) = oneshot::channel();
+        cancel_tx.send(()).unwrap();
+        let error = login.wait_for_code(Duration::from_secs(10), cancel_rx).await.unwrap_err();
+        assert!(matches!(error, AuthError::LoginCancelled));
+
+        let (_cancel_tx, cancel_rx) = oneshot::channel();
+        let error = login.wait_for_code(Duration::from_millis(10), cancel_rx).await.unwrap_err();
+        assert!(matches!(error, AuthError::LoginTimeout));
+    }
+}
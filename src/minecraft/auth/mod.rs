@@ -1 +1,17 @@
-mod azuriom;
+mod authenticator;
+mod error;
+mod profile;
+pub mod authlib_injector;
+pub mod avatar;
+pub mod azuriom;
+pub mod microsoft;
+pub mod microsoft_browser;
+pub mod offline;
+pub mod session;
+pub mod skins;
+pub mod store;
+pub mod yggdrasil;
+
+pub use authenticator::Authenticator;
+pub use error::AuthError;
+pub use profile::{GameOwnership, UserProfile, UserType};
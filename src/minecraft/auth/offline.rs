@@ -1,13 +1,73 @@
-struct OfflineAuthenticator {
+use crate::minecraft::auth::authenticator::Authenticator;
+use crate::minecraft::auth::error::AuthError;
+use crate::minecraft::auth::profile::{UserProfile, UserType};
+
+pub struct OfflineAuthenticator {
     username: String,
 }
+
 impl OfflineAuthenticator {
-    pub fn new(username: String) -> Self {
-        Self { username }
+    pub fn new(username: &str) -> Self {
+        Self { username: username.to_string() }
+    }
+}
+
+impl Authenticator for OfflineAuthenticator {
+    async fn authenticate(&self) -> Result<UserProfile, AuthError> {
+        validate_username(&self.username)?;
+        let uuid = offline_uuid(&self.username);
+        Ok(UserProfile::new(&self.username, &uuid, "0", UserType::Legacy))
     }
 
-    pub fn authenticate(&self) -> Result<OfflineProfile> {
-        let profile = OfflineProfile::new(self.username.clone());
-        Ok(profile)
+    async fn refresh(&self, profile: &UserProfile) -> Result<UserProfile, AuthError> {
+        // There is no token to refresh for an offline account
+        Ok(profile.clone())
+    }
+
+    async fn validate(&self, profile: &UserProfile) -> Result<bool, AuthError> {
+        Ok(validate_username(&profile.name).is_ok())
+    }
+}
+
+/// Check a name against Minecraft's rules: 3 to 16 characters, letters, digits and underscores
+pub fn validate_username(username: &str) -> Result<(), AuthError> {
+    let valid_length = (3..=16).contains(&username.len());
+    let valid_chars = username.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+
+    if valid_length && valid_chars {
+        Ok(())
+    } else {
+        Err(AuthError::InvalidUsername(username.to_string()))
     }
-}
\ No newline at end of file
+}
+
+/// UUID the vanilla server gives to an offline player: `UUID.nameUUIDFromBytes("OfflinePlayer:<name>")`
+pub fn offline_uuid(username: &str) -> String {
+    let mut hash = md5::compute(format!("OfflinePlayer:{}", username)).0;
+    // Version 3 (name based, MD5) and IETF variant
+    hash[6] = (hash[6] & 0x0f) | 0x30;
+    hash[8] = (hash[8] & 0x3f) | 0x80;
+
+    let hex = hex::encode(hash);
+    format!("{}-{}-{}-{}-{}", &hex[0..8], &hex[8..12], &hex[12..16], &hex[16..20], &hex[20..32])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn offline_uuid_matches_vanilla() {
+        // Value produced by the vanilla server
+        assert_eq!(offline_uuid("Notch"), "b50ad385-829d-3141-a216-7e7d7539ba7f");
+    }
+
+    #[test]
+    fn usernames_follow_minecraft_rules() {
+        assert!(validate_username("Steve_123").is_ok());
+        assert!(validate_username("ab").is_err());
+        assert!(validate_username("a_name_that_is_too_long").is_err());
+        assert!(validate_username("Hamadi!").is_err());
+        assert!(validate_username("Gaïa").is_err());
+    }
+}
@@ -0,0 +1,98 @@
+use std::collections::BTreeMap;
+use std::time::{SystemTime, UNIX_EPOCH};
+use serde::{Deserialize, Serialize};
+
+/// Kind of account, passed to the game as `--userType`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum UserType {
+    #[serde(rename = "msa")]
+    Msa,
+    #[serde(rename = "mojang")]
+    Mojang,
+    #[serde(rename = "legacy")]
+    Legacy,
+}
+
+impl UserType {
+    pub fn get_name(&self) -> &'static str {
+        match self {
+            UserType::Msa => "msa",
+            UserType::Mojang => "mojang",
+            UserType::Legacy => "legacy",
+        }
+    }
+}
+
+/// Whether the account can play Java Edition
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum GameOwnership {
+    #[serde(rename = "owned")]
+    Owned,
+    #[serde(rename = "game_pass")]
+    GamePass,
+    #[serde(rename = "not_owned")]
+    NotOwned,
+}
+
+/// Profile returned by every authentication backend and used to launch the game
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UserProfile {
+    pub name: String,
+    pub uuid: String,
+    pub access_token: String,
+    pub user_type: UserType,
+    /// Token used by the backend to get a new access token, if it supports it
+    pub refresh_token: Option<String>,
+    /// Extra properties, passed to the game as `--userProperties`
+    pub properties: BTreeMap<String, Vec<String>>,
+    #[serde(default)]
+    pub skin_url: Option<String>,
+    #[serde(default)]
+    pub cape_url: Option<String>,
+    /// Unix time in seconds after which the access token is rejected, `None` if unknown or never
+    #[serde(default)]
+    pub expires_at: Option<u64>,
+    /// Result of the entitlement check, `None` for backends that do not have one
+    #[serde(default)]
+    pub ownership: Option<GameOwnership>,
+    /// API root of the Yggdrasil server the account belongs to, the game is then started through authlib-injector
+    #[serde(default)]
+    pub yggdrasil_server: Option<String>,
+}
+
+impl UserProfile {
+    pub fn new(name: &str, uuid: &str, access_token: &str, user_type: UserType) -> Self {
+        Self {
+            name: name.to_string(),
+            uuid: uuid.to_string(),
+            access_token: access_token.to_string(),
+            user_type,
+            refresh_token: None,
+            properties: BTreeMap::new(),
+            skin_url: None,
+            cape_url: None,
+            expires_at: None,
+            ownership: None,
+            yggdrasil_server: None,
+        }
+    }
+
+    /// Accounts that do not own the game can only start it in demo mode
+    pub fn is_demo(&self) -> bool {
+        self.ownership == Some(GameOwnership::NotOwned)
+    }
+
+    /// Whether the access token is known to expire in the next `margin` seconds
+    pub fn expires_within(&self, margin: u64) -> bool {
+        self.expires_at.is_some_and(|expires_at| unix_time() + margin >= expires_at)
+    }
+
+    /// Properties serialized the way the game expects them, `{}` when empty
+    pub fn get_user_properties(&self) -> String {
+        serde_json::to_string(&self.properties).unwrap_or_else(|_| "{}".to_string())
+    }
+}
+
+pub(crate) fn unix_time() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
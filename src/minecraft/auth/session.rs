@@ -0,0 +1,217 @@
+use std::error::Error;
+use crate::minecraft::auth::authenticator::Authenticator;
+use crate::minecraft::auth::azuriom::AzuriomAuthenticator;
+use crate::minecraft::auth::error::AuthError;
+use crate::minecraft::auth::offline::OfflineAuthenticator;
+use crate::minecraft::auth::profile::UserProfile;
+use crate::minecraft::auth::store::{AccountKind, AccountStore};
+use crate::minecraft::auth::yggdrasil::YggdrasilAuthenticator;
+
+/// Tokens expiring in less than this many seconds are refreshed before launching
+const EXPIRY_MARGIN: u64 = 5 * 60;
+
+/// Outcome of the session check done before launching
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AccountEvent {
+    /// The access token is still valid, nothing was done
+    SessionValid { account_id: String },
+    /// The access token had expired and was refreshed silently
+    SessionRefreshed { account_id: String },
+    /// The refresh failed, the user has to log in again
+    RefreshFailed { account_id: String, reason: String },
+}
+
+impl AccountStore {
+    /// Make sure the active account has a usable access token and return its profile.
+    ///
+    /// A token whose expiry is stored and still far enough is used without any request.
+    /// Otherwise the backend validates it, and refreshes it when it has expired.
+    /// Fails with [`AuthError::LoginRequired`] when the backend rejects the refresh, other errors are returned as is.
+    pub async fn ensure_active_session<F>(&mut self, on_event: F) -> Result<UserProfile, Box<dyn Error + Send + Sync>>
+    where
+        F: Fn(&AccountEvent),
+    {
+        let account = self.get_active().ok_or(AuthError::LoginRequired)?.clone();
+        let profile = &account.profile;
+
+        let fresh = match profile.expires_at {
+            Some(_) => !profile.expires_within(EXPIRY_MARGIN),
+            // Only a token the backend rejects is refreshed, an unreachable backend is an error
+            None => self.validate(&account.kind, profile).await?,
+        };
+        if fresh {
+            on_event(&AccountEvent::SessionValid { account_id: account.id.clone() });
+            return Ok(account.profile);
+        }
+
+        match self.refresh(&account.kind, profile).await {
+            Ok(refreshed) => {
+                self.update_profile(&account.id, refreshed.clone()).await?;
+                on_event(&AccountEvent::SessionRefreshed { account_id: account.id });
+                Ok(refreshed)
+            }
+            Err(e) if requires_login(&e) => {
+                on_event(&AccountEvent::RefreshFailed { account_id: account.id, reason: e.to_string() });
+                Err(AuthError::LoginRequired.into())
+            }
+            // The backend could not be reached, the refresh token may still be good
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    async fn validate(&self, kind: &AccountKind, profile: &UserProfile) -> Result<bool, AuthError> {
+        match kind {
+            AccountKind::Microsoft => self.get_microsoft_authenticator().ok_or(AuthError::LoginRequired)?.validate(profile).await,
+            AccountKind::Azuriom { base_url } => AzuriomAuthenticator::from_base_url(base_url).validate(profile).await,
+            AccountKind::Yggdrasil { api_url, client_token } => YggdrasilAuthenticator::from_api_url(api_url, client_token).validate(profile).await,
+            AccountKind::Offline => OfflineAuthenticator::new(&profile.name).validate(profile).await,
+        }
+    }
+
+    async fn refresh(&self, kind: &AccountKind, profile: &UserProfile) -> Result<UserProfile, AuthError> {
+        match kind {
+            AccountKind::Microsoft => self.get_microsoft_authenticator().ok_or(AuthError::LoginRequired)?.refresh(profile).await,
+            AccountKind::Azuriom { base_url } => AzuriomAuthenticator::from_base_url(base_url).refresh(profile).await,
+            AccountKind::Yggdrasil { api_url, client_token } => YggdrasilAuthenticator::from_api_url(api_url, client_token).refresh(profile).await,
+            AccountKind::Offline => OfflineAuthenticator::new(&profile.name).refresh(profile).await,
+        }
+    }
+}
+
+/// Whether the backend rejected the refresh token, as opposed to a network or server failure
+fn requires_login(error: &AuthError) -> bool {
+    match error {
+        AuthError::InvalidCredentials | AuthError::RefreshTokenMissing | AuthError::LoginRequired => true,
+        AuthError::Http(e) => matches!(e.status(), Some(reqwest::StatusCode::BAD_REQUEST | reqwest::StatusCode::UNAUTHORIZED)),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::minecraft::auth::microsoft::tests::{mount_xbox_chain, xsts_success};
+    use crate::minecraft::auth::microsoft::{MicrosoftAuthenticator, MicrosoftEndpoints};
+    use crate::minecraft::auth::profile::{unix_time, UserType};
+    use std::sync::Mutex;
+    use wiremock::matchers::{body_string_contains, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    async fn store_with_account(server: &MockServer, dir: &tempfile::TempDir, expires_at: Option<u64>) -> AccountStore {
+        let authenticator = MicrosoftAuthenticator::new("client")
+            .with_endpoints(MicrosoftEndpoints::with_base_url(&server.uri()));
+        let mut store = AccountStore::open_with_key(&dir.path().join("accounts.json"), &[1; 32])
+            .await
+            .unwrap()
+            .with_microsoft_authenticator(authenticator);
+
+        let mut profile = UserProfile::new("Notch", "069a79f444e94726a5befca90e38aaf5", "old-token", UserType::Msa);
+        profile.refresh_token = Some("ms-refresh".to_string());
+        profile.expires_at = expires_at;
+        store.add(AccountKind::Microsoft, profile).await.unwrap();
+        store
+    }
+
+    #[tokio::test]
+    async fn fresh_token_is_used_without_request() {
+        // Any request would get a 404 from the empty server
+        let server = MockServer::start().await;
+        let dir = tempfile::tempdir().unwrap();
+        let mut store = store_with_account(&server, &dir, Some(unix_time() + 3600)).await;
+
+        let events = Mutex::new(Vec::new());
+        let profile = store.ensure_active_session(|e| events.lock().unwrap().push(e.clone())).await.unwrap();
+
+        assert_eq!(profile.access_token, "old-token");
+        assert!(matches!(events.lock().unwrap()[0], AccountEvent::SessionValid { .. }));
+        assert!(server.received_requests().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn expired_token_is_refreshed_and_saved() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/consumers/oauth2/v2.0/token"))
+            .and(body_string_contains("grant_type=refresh_token"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "access_token": "ms-access",
+                "refresh_token": "ms-refresh-2",
+                "expires_in": 3600
+            })))
+            .mount(&server)
+            .await;
+        mount_xbox_chain(&server, xsts_success()).await;
+
+        let dir = tempfile::tempdir().unwrap();
+        let mut store = store_with_account(&server, &dir, Some(unix_time() - 10)).await;
+
+        let events = Mutex::new(Vec::new());
+        let profile = store.ensure_active_session(|e| events.lock().unwrap().push(e.clone())).await.unwrap();
+
+        assert_eq!(profile.access_token, "mc-token");
+        assert!(!profile.expires_within(EXPIRY_MARGIN));
+        assert!(matches!(events.lock().unwrap()[0], AccountEvent::SessionRefreshed { .. }));
+
+        let reopened = AccountStore::open_with_key(&dir.path().join("accounts.json"), &[1; 32]).await.unwrap();
+        assert_eq!(reopened.get_active().unwrap().profile.refresh_token.as_deref(), Some("ms-refresh-2"));
+    }
+
+    #[tokio::test]
+    async fn failed_refresh_requires_login() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/consumers/oauth2/v2.0/token"))
+            .respond_with(ResponseTemplate::new(400).set_body_json(serde_json::json!({ "error": "invalid_grant" })))
+            .mount(&server)
+            .await;
+
+        let dir = tempfile::tempdir().unwrap();
+        let mut store = store_with_account(&server, &dir, Some(unix_time() - 10)).await;
+
+        let events = Mutex::new(Vec::new());
+        let error = store.ensure_active_session(|e| events.lock().unwrap().push(e.clone())).await.unwrap_err();
+
+        assert!(matches!(error.downcast_ref::<AuthError>(), Some(AuthError::LoginRequired)));
+        assert!(matches!(events.lock().unwrap()[0], AccountEvent::RefreshFailed { .. }));
+    }
+
+    #[tokio::test]
+    async fn unreachable_backend_does_not_log_out() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/minecraft/profile"))
+            .respond_with(ResponseTemplate::new(503))
+            .mount(&server)
+            .await;
+
+        let dir = tempfile::tempdir().unwrap();
+        let mut store = store_with_account(&server, &dir, None).await;
+
+        let events = Mutex::new(Vec::new());
+        let error = store.ensure_active_session(|e| events.lock().unwrap().push(e.clone())).await.unwrap_err();
+
+        assert!(!matches!(error.downcast_ref::<AuthError>(), Some(AuthError::LoginRequired)));
+        assert!(events.lock().unwrap().is_empty());
+        let requests = server.received_requests().await.unwrap();
+        assert!(requests.iter().all(|request| request.url.path() == "/minecraft/profile"));
+    }
+
+    #[tokio::test]
+    async fn server_errors_during_refresh_do_not_log_out() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/consumers/oauth2/v2.0/token"))
+            .respond_with(ResponseTemplate::new(503))
+            .mount(&server)
+            .await;
+
+        let dir = tempfile::tempdir().unwrap();
+        let mut store = store_with_account(&server, &dir, Some(unix_time() - 10)).await;
+
+        let events = Mutex::new(Vec::new());
+        let error = store.ensure_active_session(|e| events.lock().unwrap().push(e.clone())).await.unwrap_err();
+
+        assert!(!matches!(error.downcast_ref::<AuthError>(), Some(AuthError::LoginRequired)));
+        assert!(events.lock().unwrap().is_empty());
+    }
+}
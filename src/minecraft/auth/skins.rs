@@ -0,0 +1,319 @@
+use std::path::Path;
+use reqwest::multipart::{Form, Part};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tokio::fs;
+use crate::minecraft::auth::error::AuthError;
+use crate::minecraft::auth::profile::UserProfile;
+use crate::utils::hosts::HTTP_CLIENT;
+
+const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+/// Skins larger than this are refused by the API
+const MAX_SKIN_SIZE: usize = 24 * 1024;
+
+/// Endpoints of the Minecraft services used to manage skins and capes.
+///
+/// Every URL can be overridden, which allows the module to run against a local server.
+#[derive(Debug, Clone)]
+pub struct SkinEndpoints {
+    pub profile_url: String,
+    pub skins_url: String,
+    pub active_skin_url: String,
+    pub active_cape_url: String,
+}
+
+impl Default for SkinEndpoints {
+    fn default() -> Self {
+        Self::with_base_url("https://api.minecraftservices.com")
+    }
+}
+
+impl SkinEndpoints {
+    /// Point every endpoint to the same base URL, keeping the official paths
+    pub fn with_base_url(base_url: &str) -> Self {
+        let base_url = base_url.trim_end_matches('/');
+        Self {
+            profile_url: format!("{}/minecraft/profile", base_url),
+            skins_url: format!("{}/minecraft/profile/skins", base_url),
+            active_skin_url: format!("{}/minecraft/profile/skins/active", base_url),
+            active_cape_url: format!("{}/minecraft/profile/capes/active", base_url),
+        }
+    }
+}
+
+/// Arm width of a skin: 4 pixels for classic (Steve), 3 for slim (Alex)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SkinVariant {
+    #[serde(rename = "CLASSIC", alias = "classic")]
+    Classic,
+    #[serde(rename = "SLIM", alias = "slim")]
+    Slim,
+}
+
+impl SkinVariant {
+    pub fn get_name(&self) -> &'static str {
+        match self {
+            SkinVariant::Classic => "classic",
+            SkinVariant::Slim => "slim",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TextureState {
+    #[serde(rename = "ACTIVE")]
+    Active,
+    #[serde(rename = "INACTIVE")]
+    Inactive,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Skin {
+    pub id: String,
+    pub state: TextureState,
+    pub url: String,
+    pub texture_key: Option<String>,
+    pub variant: SkinVariant,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Cape {
+    pub id: String,
+    pub state: TextureState,
+    pub url: String,
+    pub alias: Option<String>,
+}
+
+/// Minecraft profile with its skins and capes, as returned by the Minecraft services
+#[derive(Debug, Clone, Deserialize)]
+pub struct MinecraftProfile {
+    pub id: String,
+    pub name: String,
+    #[serde(default)]
+    pub skins: Vec<Skin>,
+    #[serde(default)]
+    pub capes: Vec<Cape>,
+}
+
+impl MinecraftProfile {
+    pub fn get_active_skin(&self) -> Option<&Skin> {
+        self.skins.iter().find(|skin| skin.state == TextureState::Active)
+    }
+
+    /// `None` when the cape is hidden or the player has none
+    pub fn get_active_cape(&self) -> Option<&Cape> {
+        self.capes.iter().find(|cape| cape.state == TextureState::Active)
+    }
+
+    /// Copy the active skin and cape URLs to a launcher profile
+    pub fn update_user_profile(&self, profile: &mut UserProfile) {
+        profile.skin_url = self.get_active_skin().map(|skin| skin.url.clone());
+        profile.cape_url = self.get_active_cape().map(|cape| cape.url.clone());
+    }
+}
+
+/// Skins and capes of a Microsoft account, using its Minecraft access token
+pub struct SkinManager {
+    access_token: String,
+    endpoints: SkinEndpoints,
+}
+
+impl SkinManager {
+    pub fn new(profile: &UserProfile) -> Self {
+        Self {
+            access_token: profile.access_token.clone(),
+            endpoints: SkinEndpoints::default(),
+        }
+    }
+
+    pub fn with_endpoints(mut self, endpoints: SkinEndpoints) -> Self {
+        self.endpoints = endpoints;
+        self
+    }
+
+    pub fn get_endpoints(&self) -> &SkinEndpoints {
+        &self.endpoints
+    }
+
+    pub async fn get_profile(&self) -> Result<MinecraftProfile, AuthError> {
+        self.send(HTTP_CLIENT.get(&self.endpoints.profile_url)).await
+    }
+
+    /// Upload a skin PNG, it is validated before anything is sent
+    pub async fn upload_skin(&self, png: Vec<u8>, variant: SkinVariant) -> Result<MinecraftProfile, AuthError> {
+        validate_skin_png(&png)?;
+
+        let file = Part::bytes(png)
+            .file_name("skin.png")
+            .mime_str("image/png")?;
+        let form = Form::new()
+            .text("variant", variant.get_name())
+            .part("file", file);
+        self.send(HTTP_CLIENT.post(&self.endpoints.skins_url).multipart(form)).await
+    }
+
+    pub async fn upload_skin_file(&self, path: &Path, variant: SkinVariant) -> Result<MinecraftProfile, AuthError> {
+        let png = fs::read(path).await?;
+        self.upload_skin(png, variant).await
+    }
+
+    /// Go back to the default skin
+    pub async fn reset_skin(&self) -> Result<MinecraftProfile, AuthError> {
+        self.send(HTTP_CLIENT.delete(&self.endpoints.active_skin_url)).await
+    }
+
+    /// Show one of the capes owned by the player
+    pub async fn show_cape(&self, cape_id: &str) -> Result<MinecraftProfile, AuthError> {
+        let body = json!({ "capeId": cape_id });
+        self.send(HTTP_CLIENT.put(&self.endpoints.active_cape_url).json(&body)).await
+    }
+
+    pub async fn hide_cape(&self) -> Result<MinecraftProfile, AuthError> {
+        self.send(HTTP_CLIENT.delete(&self.endpoints.active_cape_url)).await
+    }
+
+    async fn send(&self, request: reqwest::RequestBuilder) -> Result<MinecraftProfile, AuthError> {
+        let response = request.bearer_auth(&self.access_token).send().await?;
+        match response.status() {
+            reqwest::StatusCode::UNAUTHORIZED => Err(AuthError::LoginRequired),
+            reqwest::StatusCode::NOT_FOUND => Err(AuthError::NoMinecraftProfile),
+            _ => Ok(response.error_for_status()?.json().await?),
+        }
+    }
+}
+
+/// Check that `png` is a skin the game accepts: a PNG of 64x64, or 64x32 for legacy skins.
+///
+/// Returns its width and height.
+pub fn validate_skin_png(png: &[u8]) -> Result<(u32, u32), AuthError> {
+    if png.len() > MAX_SKIN_SIZE {
+        return Err(AuthError::InvalidSkin(format!("the file is larger than {} KB", MAX_SKIN_SIZE / 1024)));
+    }
+    if !png.starts_with(PNG_SIGNATURE) {
+        return Err(AuthError::InvalidSkin("the file is not a PNG image".to_string()));
+    }
+
+    // The IHDR chunk always comes first: length (4), type (4), width (4), height (4)
+    let header = png.get(8..24).ok_or_else(|| AuthError::InvalidSkin("the PNG header is truncated".to_string()))?;
+    if &header[4..8] != b"IHDR" {
+        return Err(AuthError::InvalidSkin("the PNG header is missing".to_string()));
+    }
+    let width = u32::from_be_bytes(header[8..12].try_into().unwrap());
+    let height = u32::from_be_bytes(header[12..16].try_into().unwrap());
+
+    match (width, height) {
+        (64, 64) | (64, 32) => Ok((width, height)),
+        _ => Err(AuthError::InvalidSkin(format!("the image is {}x{}, skins must be 64x64 or 64x32", width, height))),
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::minecraft::auth::profile::UserType;
+    use wiremock::matchers::{body_json, header, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    /// Header of a PNG with the given size, enough for the local validation
+    pub(crate) fn png_header(width: u32, height: u32) -> Vec<u8> {
+        let mut png = PNG_SIGNATURE.to_vec();
+        png.extend_from_slice(&13u32.to_be_bytes());
+        png.extend_from_slice(b"IHDR");
+        png.extend_from_slice(&width.to_be_bytes());
+        png.extend_from_slice(&height.to_be_bytes());
+        png.extend_from_slice(&[8, 6, 0, 0, 0]);
+        png
+    }
+
+    fn profile_json(cape_state: &str) -> serde_json::Value {
+        json!({
+            "id": "069a79f444e94726a5befca90e38aaf5",
+            "name": "Notch",
+            "skins": [{
+                "id": "skin-id",
+                "state": "ACTIVE",
+                "url": "http://textures.minecraft.net/texture/skin",
+                "textureKey": "skin",
+                "variant": "SLIM"
+            }],
+            "capes": [{
+                "id": "cape-id",
+                "state": cape_state,
+                "url": "http://textures.minecraft.net/texture/cape",
+                "alias": "Migrator"
+            }]
+        })
+    }
+
+    fn manager(server: &MockServer) -> SkinManager {
+        let profile = UserProfile::new("Notch", "069a79f444e94726a5befca90e38aaf5", "mc-token", UserType::Msa);
+        SkinManager::new(&profile).with_endpoints(SkinEndpoints::with_base_url(&server.uri()))
+    }
+
+    #[test]
+    fn skin_dimensions_are_checked() {
+        assert_eq!(validate_skin_png(&png_header(64, 64)).unwrap(), (64, 64));
+        assert_eq!(validate_skin_png(&png_header(64, 32)).unwrap(), (64, 32));
+        assert!(matches!(validate_skin_png(&png_header(128, 128)), Err(AuthError::InvalidSkin(_))));
+        assert!(matches!(validate_skin_png(b"GIF89a"), Err(AuthError::InvalidSkin(_))));
+        assert!(matches!(validate_skin_png(&png_header(64, 64)[..16]), Err(AuthError::InvalidSkin(_))));
+    }
+
+    #[tokio::test]
+    async fn skin_upload_and_cape_toggle() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/minecraft/profile/skins"))
+            .and(header("authorization", "Bearer mc-token"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(profile_json("INACTIVE")))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("PUT"))
+            .and(path("/minecraft/profile/capes/active"))
+            .and(body_json(json!({ "capeId": "cape-id" })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(profile_json("ACTIVE")))
+            .mount(&server)
+            .await;
+        Mock::given(method("DELETE"))
+            .and(path("/minecraft/profile/capes/active"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(profile_json("INACTIVE")))
+            .mount(&server)
+            .await;
+
+        let manager = manager(&server);
+
+        // Rejected locally, the mock expects a single upload
+        assert!(manager.upload_skin(png_header(32, 32), SkinVariant::Slim).await.is_err());
+        let profile = manager.upload_skin(png_header(64, 64), SkinVariant::Slim).await.unwrap();
+        assert_eq!(profile.get_active_skin().unwrap().variant, SkinVariant::Slim);
+        assert!(profile.get_active_cape().is_none());
+
+        // The multipart body holds binary PNG data, look for the variant field directly
+        let upload = &server.received_requests().await.unwrap()[0];
+        assert!(upload.body.windows(4).any(|window| window == b"slim"));
+
+        let profile = manager.show_cape("cape-id").await.unwrap();
+        assert_eq!(profile.get_active_cape().unwrap().alias.as_deref(), Some("Migrator"));
+
+        let mut user = UserProfile::new("Notch", "069a79f444e94726a5befca90e38aaf5", "mc-token", UserType::Msa);
+        profile.update_user_profile(&mut user);
+        assert_eq!(user.cape_url.as_deref(), Some("http://textures.minecraft.net/texture/cape"));
+
+        let profile = manager.hide_cape().await.unwrap();
+        assert!(profile.get_active_cape().is_none());
+    }
+
+    #[tokio::test]
+    async fn expired_token_requires_login() {
+        let server = MockServer::start().await;
+        Mock::given(method("DELETE"))
+            .and(path("/minecraft/profile/skins/active"))
+            .respond_with(ResponseTemplate::new(401))
+            .mount(&server)
+            .await;
+
+        assert!(matches!(manager(&server).reset_skin().await, Err(AuthError::LoginRequired)));
+    }
+}
@@ -0,0 +1,366 @@
+use std::error::Error;
+use std::path::{Path, PathBuf};
+use aes_gcm::aead::{Aead, KeyInit};
+use aes_gcm::{Aes256Gcm, Nonce};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use directories::ProjectDirs;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use tokio::fs;
+use tokio::io::AsyncWriteExt;
+use crate::minecraft::auth::error::AuthError;
+use crate::minecraft::auth::microsoft::MicrosoftAuthenticator;
+use crate::minecraft::auth::profile::UserProfile;
+
+/// Current layout of `accounts.json`.
+///
+/// - 0: unversioned list of accounts with plaintext tokens
+/// - 1: `version` field, tokens encrypted with the machine key
+const STORE_VERSION: u64 = 1;
+const STORE_FILE: &str = "accounts.json";
+
+/// Backend an account logs in with, needed to refresh it later
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum AccountKind {
+    Microsoft,
+    Azuriom { base_url: String },
+    /// Yggdrasil tokens are bound to the client token they were issued for
+    Yggdrasil { api_url: String, client_token: String },
+    Offline,
+}
+
+impl AccountKind {
+    pub fn get_name(&self) -> &'static str {
+        match self {
+            AccountKind::Microsoft => "microsoft",
+            AccountKind::Azuriom { .. } => "azuriom",
+            AccountKind::Yggdrasil { .. } => "yggdrasil",
+            AccountKind::Offline => "offline",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Account {
+    pub id: String,
+    pub kind: AccountKind,
+    pub profile: UserProfile,
+    pub active: bool,
+}
+
+/// Account as written on disk, secrets are encrypted
+#[derive(Debug, Serialize, Deserialize)]
+struct StoredAccount {
+    id: String,
+    kind: AccountKind,
+    active: bool,
+    /// Profile with its tokens blanked
+    profile: Value,
+    access_token: Option<String>,
+    refresh_token: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct StoreFile {
+    version: u64,
+    accounts: Vec<StoredAccount>,
+}
+
+/// Accounts remembered across launcher restarts, saved in `accounts.json` in the launcher data directory
+pub struct AccountStore {
+    path: PathBuf,
+    cipher: Aes256Gcm,
+    accounts: Vec<Account>,
+    microsoft: Option<MicrosoftAuthenticator>,
+}
+
+impl AccountStore {
+    /// Open the store of the launcher, encrypted with the key of this machine
+    pub async fn open(project_dirs: &Lazy<ProjectDirs>) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        let key = machine_key()?;
+        Self::open_with_key(&project_dirs.data_dir().join(STORE_FILE), &key).await
+    }
+
+    /// Open a store at `path` with an explicit 32 bytes key
+    pub async fn open_with_key(path: &Path, key: &[u8; 32]) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        let cipher = Aes256Gcm::new_from_slice(key).map_err(|e| e.to_string())?;
+        let mut store = Self { path: path.to_path_buf(), cipher, accounts: Vec::new(), microsoft: None };
+
+        if !path.exists() {
+            return Ok(store);
+        }
+
+        let content = fs::read_to_string(path).await?;
+        let value: Value = serde_json::from_str(&content)?;
+        let (file, migrated) = store.migrate(value)?;
+
+        for stored in file.accounts {
+            let mut profile: UserProfile = serde_json::from_value(stored.profile)?;
+            profile.access_token = store.decrypt(stored.access_token.as_deref())?.unwrap_or_default();
+            profile.refresh_token = store.decrypt(stored.refresh_token.as_deref())?;
+            store.accounts.push(Account { id: stored.id, kind: stored.kind, profile, active: stored.active });
+        }
+
+        if migrated {
+            store.save().await?;
+        }
+        Ok(store)
+    }
+
+    /// Authenticator used to refresh Microsoft accounts, it must use the client ID they logged in with
+    pub fn with_microsoft_authenticator(mut self, authenticator: MicrosoftAuthenticator) -> Self {
+        self.microsoft = Some(authenticator);
+        self
+    }
+
+    pub fn get_microsoft_authenticator(&self) -> Option<&MicrosoftAuthenticator> {
+        self.microsoft.as_ref()
+    }
+
+    pub fn list(&self) -> &[Account] {
+        &self.accounts
+    }
+
+    pub fn get(&self, id: &str) -> Option<&Account> {
+        self.accounts.iter().find(|account| account.id == id)
+    }
+
+    pub fn get_active(&self) -> Option<&Account> {
+        self.accounts.iter().find(|account| account.active)
+    }
+
+    /// Add an account, or update it if it is already stored, and make it the active one
+    pub async fn add(&mut self, kind: AccountKind, profile: UserProfile) -> Result<&Account, Box<dyn Error + Send + Sync>> {
+        let id = format!("{}:{}", kind.get_name(), profile.uuid);
+        self.accounts.retain(|account| account.id != id);
+        for account in &mut self.accounts {
+            account.active = false;
+        }
+        self.accounts.push(Account { id, kind, profile, active: true });
+        self.save().await?;
+        Ok(self.accounts.last().unwrap())
+    }
+
+    /// Remove an account, returns `false` if it was not stored
+    pub async fn remove(&mut self, id: &str) -> Result<bool, Box<dyn Error + Send + Sync>> {
+        let len = self.accounts.len();
+        self.accounts.retain(|account| account.id != id);
+        if self.accounts.len() == len {
+            return Ok(false);
+        }
+
+        // Keep an active account as long as there is one
+        if self.get_active().is_none()
+            && let Some(account) = self.accounts.first_mut()
+        {
+            account.active = true;
+        }
+        self.save().await?;
+        Ok(true)
+    }
+
+    /// Make `id` the active account
+    pub async fn select(&mut self, id: &str) -> Result<(), Box<dyn Error + Send + Sync>> {
+        if self.get(id).is_none() {
+            return Err(format!("Account {} not found", id).into());
+        }
+        for account in &mut self.accounts {
+            account.active = account.id == id;
+        }
+        self.save().await
+    }
+
+    /// Replace the profile of a stored account, e.g. after a token refresh
+    pub async fn update_profile(&mut self, id: &str, profile: UserProfile) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let account = self
+            .accounts
+            .iter_mut()
+            .find(|account| account.id == id)
+            .ok_or(format!("Account {} not found", id))?;
+        account.profile = profile;
+        self.save().await
+    }
+
+    /// Write the store atomically: a temporary file is written, flushed, then renamed over the old one
+    async fn save(&self) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let mut accounts = Vec::with_capacity(self.accounts.len());
+        for account in &self.accounts {
+            let mut profile = account.profile.clone();
+            profile.access_token = String::new();
+            profile.refresh_token = None;
+            accounts.push(StoredAccount {
+                id: account.id.clone(),
+                kind: account.kind.clone(),
+                active: account.active,
+                profile: serde_json::to_value(profile)?,
+                access_token: Some(self.encrypt(&account.profile.access_token)?),
+                refresh_token: account.profile.refresh_token.as_deref().map(|token| self.encrypt(token)).transpose()?,
+            });
+        }
+        let content = serde_json::to_vec_pretty(&StoreFile { version: STORE_VERSION, accounts })?;
+
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent).await?;
+        }
+        let tmp_path = self.path.with_extension("json.tmp");
+        let mut file = fs::File::create(&tmp_path).await?;
+        file.write_all(&content).await?;
+        file.sync_all().await?;
+        drop(file);
+        fs::rename(&tmp_path, &self.path).await?;
+        Ok(())
+    }
+
+    /// Bring an older layout up to [`STORE_VERSION`], returns whether something changed
+    fn migrate(&self, mut value: Value) -> Result<(StoreFile, bool), Box<dyn Error + Send + Sync>> {
+        let mut version = value["version"].as_u64().unwrap_or(0);
+        if version > STORE_VERSION {
+            return Err(format!("Account store version {} is newer than this launcher ({})", version, STORE_VERSION).into());
+        }
+        let migrated = version < STORE_VERSION;
+
+        while version < STORE_VERSION {
+            value = match version {
+                0 => self.migrate_v0(value)?,
+                _ => unreachable!(),
+            };
+            version += 1;
+        }
+        Ok((serde_json::from_value(value)?, migrated))
+    }
+
+    /// Version 0 stored the whole profile, tokens included, in clear text
+    fn migrate_v0(&self, value: Value) -> Result<Value, Box<dyn Error + Send + Sync>> {
+        let mut accounts = Vec::new();
+        // Anything else is a corrupted or unknown file, migrating it would wipe the accounts
+        let accounts_v0 = value.as_array().cloned().ok_or_else(|| AuthError::Store("unsupported store format".to_string()))?;
+        for mut account in accounts_v0 {
+            let profile = account["profile"].as_object_mut().ok_or("Invalid account in store")?;
+            let access_token = profile.insert("access_token".to_string(), Value::from("")).and_then(|token| token.as_str().map(str::to_string));
+            let refresh_token = profile.insert("refresh_token".to_string(), Value::Null).and_then(|token| token.as_str().map(str::to_string));
+            account["access_token"] = serde_json::to_value(access_token.map(|token| self.encrypt(&token)).transpose()?)?;
+            account["refresh_token"] = serde_json::to_value(refresh_token.map(|token| self.encrypt(&token)).transpose()?)?;
+            accounts.push(account);
+        }
+        Ok(serde_json::json!({ "version": 1, "accounts": accounts }))
+    }
+
+    fn encrypt(&self, plaintext: &str) -> Result<String, Box<dyn Error + Send + Sync>> {
+        let nonce: [u8; 12] = rand::random();
+        let ciphertext = self
+            .cipher
+            .encrypt(Nonce::from_slice(&nonce), plaintext.as_bytes())
+            .map_err(|_| "Failed to encrypt token")?;
+        Ok(STANDARD.encode([nonce.as_slice(), &ciphertext].concat()))
+    }
+
+    fn decrypt(&self, encoded: Option<&str>) -> Result<Option<String>, Box<dyn Error + Send + Sync>> {
+        let Some(encoded) = encoded else {
+            return Ok(None);
+        };
+        let data = STANDARD.decode(encoded)?;
+        if data.len() < 12 {
+            return Err("Invalid encrypted token".into());
+        }
+        let (nonce, ciphertext) = data.split_at(12);
+        let plaintext = self
+            .cipher
+            .decrypt(Nonce::from_slice(nonce), ciphertext)
+            .map_err(|_| "Failed to decrypt token, the account store was created on another machine")?;
+        Ok(Some(String::from_utf8(plaintext)?))
+    }
+}
+
+/// Key bound to this machine, derived from its machine ID
+fn machine_key() -> Result<[u8; 32], Box<dyn Error + Send + Sync>> {
+    let machine_id = machine_uid::get().map_err(|e| format!("Failed to read the machine ID: {}", e))?;
+    let mut hasher = Sha256::new();
+    hasher.update(b"LightyLauncher account store");
+    hasher.update(machine_id.trim().as_bytes());
+    Ok(hasher.finalize().into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::minecraft::auth::profile::UserType;
+
+    const KEY: [u8; 32] = [7; 32];
+
+    fn microsoft_profile() -> UserProfile {
+        let mut profile = UserProfile::new("Notch", "069a79f444e94726a5befca90e38aaf5", "mc-token", UserType::Msa);
+        profile.refresh_token = Some("ms-refresh".to_string());
+        profile
+    }
+
+    #[tokio::test]
+    async fn accounts_survive_reopening_and_tokens_are_encrypted() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(STORE_FILE);
+
+        let mut store = AccountStore::open_with_key(&path, &KEY).await.unwrap();
+        store.add(AccountKind::Microsoft, microsoft_profile()).await.unwrap();
+        store.add(AccountKind::Offline, UserProfile::new("Steve", "uuid", "0", UserType::Legacy)).await.unwrap();
+        store.select("microsoft:069a79f444e94726a5befca90e38aaf5").await.unwrap();
+
+        let content = std::fs::read_to_string(&path).unwrap();
+        assert!(!content.contains("ms-refresh"));
+        assert!(!content.contains("mc-token"));
+
+        let store = AccountStore::open_with_key(&path, &KEY).await.unwrap();
+        assert_eq!(store.list().len(), 2);
+        let active = store.get_active().unwrap();
+        assert_eq!(active.profile.name, "Notch");
+        assert_eq!(active.profile.refresh_token.as_deref(), Some("ms-refresh"));
+
+        assert!(AccountStore::open_with_key(&path, &[8; 32]).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn removing_the_active_account_activates_another_one() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut store = AccountStore::open_with_key(&dir.path().join(STORE_FILE), &KEY).await.unwrap();
+        store.add(AccountKind::Offline, UserProfile::new("Steve", "uuid", "0", UserType::Legacy)).await.unwrap();
+        store.add(AccountKind::Microsoft, microsoft_profile()).await.unwrap();
+
+        assert!(store.remove("microsoft:069a79f444e94726a5befca90e38aaf5").await.unwrap());
+        assert!(!store.remove("microsoft:069a79f444e94726a5befca90e38aaf5").await.unwrap());
+        assert_eq!(store.get_active().unwrap().id, "offline:uuid");
+    }
+
+    #[tokio::test]
+    async fn unversioned_store_is_migrated() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(STORE_FILE);
+        let legacy = serde_json::json!([{
+            "id": "microsoft:069a79f444e94726a5befca90e38aaf5",
+            "kind": { "type": "microsoft" },
+            "active": true,
+            "profile": serde_json::to_value(microsoft_profile()).unwrap(),
+        }]);
+        std::fs::write(&path, legacy.to_string()).unwrap();
+
+        let store = AccountStore::open_with_key(&path, &KEY).await.unwrap();
+        assert_eq!(store.get_active().unwrap().profile.refresh_token.as_deref(), Some("ms-refresh"));
+
+        let content: Value = serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(content["version"], STORE_VERSION);
+        assert!(!content.to_string().contains("ms-refresh"));
+    }
+
+    #[tokio::test]
+    async fn unknown_store_format_is_left_untouched() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(STORE_FILE);
+        let content = r#"{"accounts": [{"id": "offline:Steve"}]}"#;
+        std::fs::write(&path, content).unwrap();
+
+        let error = AccountStore::open_with_key(&path, &KEY).await.err().unwrap();
+        assert!(matches!(error.downcast_ref::<AuthError>(), Some(AuthError::Store(_))));
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), content);
+    }
+}
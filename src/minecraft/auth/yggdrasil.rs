@@ -0,0 +1,294 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use crate::minecraft::auth::authenticator::Authenticator;
+use crate::minecraft::auth::error::AuthError;
+use crate::minecraft::auth::profile::{UserProfile, UserType};
+use crate::utils::hosts::HTTP_CLIENT;
+
+/// Header used by servers to point to their API root (API Location Indication)
+const API_LOCATION_HEADER: &str = "x-authlib-injector-api-location";
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct YggdrasilResponse {
+    access_token: String,
+    #[serde(default)]
+    available_profiles: Vec<YggdrasilProfile>,
+    selected_profile: Option<YggdrasilProfile>,
+    user: Option<YggdrasilUser>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct YggdrasilProfile {
+    id: String,
+    name: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct YggdrasilUser {
+    #[serde(default)]
+    properties: Vec<YggdrasilProperty>,
+}
+
+#[derive(Debug, Deserialize)]
+struct YggdrasilProperty {
+    name: String,
+    value: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct YggdrasilError {
+    error: String,
+    error_message: Option<String>,
+}
+
+/// Authentication against a Yggdrasil compatible server (Drasl, Blessing Skin, ...).
+///
+/// Profiles are launched through authlib-injector, see [`super::authlib_injector`].
+pub struct YggdrasilAuthenticator {
+    api_url: String,
+    username: String,
+    password: String,
+    client_token: String,
+}
+
+impl YggdrasilAuthenticator {
+    /// `api_url` is the API root of the server, see [`resolve_api_url`] to get it from the website URL
+    pub fn new(api_url: &str, username: &str, password: &str) -> Self {
+        Self {
+            api_url: api_url.trim_end_matches('/').to_string(),
+            username: username.to_string(),
+            password: password.to_string(),
+            client_token: hex::encode(rand::random::<[u8; 16]>()),
+        }
+    }
+
+    /// Authenticator without credentials, enough to refresh or validate a stored session
+    pub fn from_api_url(api_url: &str, client_token: &str) -> Self {
+        Self::new(api_url, "", "").with_client_token(client_token)
+    }
+
+    /// The server binds tokens to the client token, the same one must be used to refresh them
+    pub fn with_client_token(mut self, client_token: &str) -> Self {
+        self.client_token = client_token.to_string();
+        self
+    }
+
+    pub fn get_api_url(&self) -> &str {
+        &self.api_url
+    }
+
+    pub fn get_client_token(&self) -> &str {
+        &self.client_token
+    }
+
+    /// Invalidate every token of the account
+    pub async fn sign_out(&self) -> Result<(), AuthError> {
+        self.post("authserver/signout", json!({ "username": self.username, "password": self.password })).await?;
+        Ok(())
+    }
+
+    async fn post(&self, endpoint: &str, body: Value) -> Result<reqwest::Response, AuthError> {
+        let response = HTTP_CLIENT
+            .post(format!("{}/{}", self.api_url, endpoint))
+            .json(&body)
+            .send()
+            .await?;
+
+        if response.status().is_success() {
+            return Ok(response);
+        }
+
+        let status = response.status();
+        Err(match response.json::<YggdrasilError>().await {
+            Ok(error) if error.error == "ForbiddenOperationException" => AuthError::InvalidCredentials,
+            Ok(error) => AuthError::InvalidResponse(error.error_message.unwrap_or(error.error)),
+            Err(_) => AuthError::InvalidResponse(format!("HTTP {}", status)),
+        })
+    }
+
+    async fn refresh_token(&self, access_token: &str, profile: Option<&YggdrasilProfile>) -> Result<YggdrasilResponse, AuthError> {
+        let mut body = json!({
+            "accessToken": access_token,
+            "clientToken": self.client_token,
+            "requestUser": true,
+        });
+        if let Some(profile) = profile {
+            body["selectedProfile"] = json!(profile);
+        }
+        Ok(self.post("authserver/refresh", body).await?.json().await?)
+    }
+
+    async fn to_profile(&self, mut response: YggdrasilResponse) -> Result<UserProfile, AuthError> {
+        // Accounts with several characters must bind the token to one of them
+        if response.selected_profile.is_none() {
+            let first = response.available_profiles.first().cloned().ok_or(AuthError::NoMinecraftProfile)?;
+            response = self.refresh_token(&response.access_token, Some(&first)).await?;
+        }
+        let selected = response.selected_profile.ok_or(AuthError::NoMinecraftProfile)?;
+
+        let mut profile = UserProfile::new(&selected.name, &selected.id, &response.access_token, UserType::Mojang);
+        profile.yggdrasil_server = Some(self.api_url.clone());
+        for property in response.user.map(|user| user.properties).unwrap_or_default() {
+            profile.properties.entry(property.name).or_default().push(property.value);
+        }
+        Ok(profile)
+    }
+}
+
+impl Authenticator for YggdrasilAuthenticator {
+    async fn authenticate(&self) -> Result<UserProfile, AuthError> {
+        let body = json!({
+            "username": self.username,
+            "password": self.password,
+            "clientToken": self.client_token,
+            "requestUser": true,
+            "agent": { "name": "Minecraft", "version": 1 },
+        });
+        let response = self.post("authserver/authenticate", body).await?.json().await?;
+        self.to_profile(response).await
+    }
+
+    async fn refresh(&self, profile: &UserProfile) -> Result<UserProfile, AuthError> {
+        let response = self.refresh_token(&profile.access_token, None).await?;
+        self.to_profile(response).await
+    }
+
+    async fn validate(&self, profile: &UserProfile) -> Result<bool, AuthError> {
+        let body = json!({ "accessToken": profile.access_token, "clientToken": self.client_token });
+        match self.post("authserver/validate", body).await {
+            Ok(_) => Ok(true),
+            Err(AuthError::InvalidCredentials) => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+/// Follow the API Location Indication of a server: users can enter the website URL instead of the API root
+pub async fn resolve_api_url(url: &str) -> Result<String, AuthError> {
+    let url = if url.starts_with("http://") || url.starts_with("https://") {
+        url.to_string()
+    } else {
+        format!("https://{}", url)
+    };
+
+    let response = HTTP_CLIENT.get(&url).send().await?;
+    let location = response
+        .headers()
+        .get(API_LOCATION_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_string);
+
+    let api_url = match location {
+        Some(location) => response
+            .url()
+            .join(&location)
+            .map_err(|e| AuthError::InvalidResponse(e.to_string()))?
+            .to_string(),
+        None => url,
+    };
+    Ok(api_url.trim_end_matches('/').to_string())
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use wiremock::matchers::{body_partial_json, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    /// Minimal Yggdrasil server with one account owning two characters
+    pub(crate) async fn mount_yggdrasil(server: &MockServer) {
+        Mock::given(method("GET"))
+            .and(path("/api/yggdrasil"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "meta": { "serverName": "Test server" },
+                "skinDomains": ["localhost"],
+                "signaturePublickey": "-----BEGIN PUBLIC KEY-----\n-----END PUBLIC KEY-----"
+            })))
+            .mount(server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/api/yggdrasil/authserver/authenticate"))
+            .and(body_partial_json(json!({ "username": "hamadi@example.com", "password": "password" })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "accessToken": "unbound-token",
+                "clientToken": "client",
+                "availableProfiles": [
+                    { "id": "37fefc811e264d31a98874196affc99b", "name": "Hamadi" },
+                    { "id": "069a79f444e94726a5befca90e38aaf5", "name": "Alt" }
+                ],
+                "user": { "id": "user", "properties": [{ "name": "preferredLanguage", "value": "fr" }] }
+            })))
+            .mount(server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/api/yggdrasil/authserver/authenticate"))
+            .respond_with(ResponseTemplate::new(403).set_body_json(json!({
+                "error": "ForbiddenOperationException",
+                "errorMessage": "Invalid credentials. Invalid username or password."
+            })))
+            .mount(server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/api/yggdrasil/authserver/refresh"))
+            .and(body_partial_json(json!({ "clientToken": "client" })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "accessToken": "bound-token",
+                "clientToken": "client",
+                "selectedProfile": { "id": "37fefc811e264d31a98874196affc99b", "name": "Hamadi" },
+                "user": { "id": "user", "properties": [{ "name": "preferredLanguage", "value": "fr" }] }
+            })))
+            .mount(server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/api/yggdrasil/authserver/validate"))
+            .and(body_partial_json(json!({ "accessToken": "bound-token" })))
+            .respond_with(ResponseTemplate::new(204))
+            .mount(server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/api/yggdrasil/authserver/validate"))
+            .respond_with(ResponseTemplate::new(403).set_body_json(json!({
+                "error": "ForbiddenOperationException",
+                "errorMessage": "Invalid token."
+            })))
+            .mount(server)
+            .await;
+    }
+
+    #[tokio::test]
+    async fn authenticate_binds_a_profile_and_validates() {
+        let server = MockServer::start().await;
+        mount_yggdrasil(&server).await;
+        let api_url = format!("{}/api/yggdrasil", server.uri());
+
+        let authenticator = YggdrasilAuthenticator::new(&api_url, "hamadi@example.com", "password").with_client_token("client");
+        let mut profile = authenticator.authenticate().await.unwrap();
+
+        assert_eq!(profile.name, "Hamadi");
+        assert_eq!(profile.access_token, "bound-token");
+        assert_eq!(profile.yggdrasil_server.as_deref(), Some(api_url.as_str()));
+        assert_eq!(profile.properties["preferredLanguage"], vec!["fr".to_string()]);
+        assert!(authenticator.validate(&profile).await.unwrap());
+
+        profile.access_token = "revoked-token".to_string();
+        assert!(!authenticator.validate(&profile).await.unwrap());
+
+        let wrong = YggdrasilAuthenticator::new(&api_url, "hamadi@example.com", "wrong");
+        assert!(matches!(wrong.authenticate().await.unwrap_err(), AuthError::InvalidCredentials));
+    }
+
+    #[tokio::test]
+    async fn api_location_is_followed() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/"))
+            .respond_with(ResponseTemplate::new(200).insert_header("X-Authlib-Injector-API-Location", "/api/yggdrasil/"))
+            .mount(&server)
+            .await;
+
+        let api_url = resolve_api_url(&server.uri()).await.unwrap();
+        assert_eq!(api_url, format!("{}/api/yggdrasil", server.uri()));
+    }
+}
@@ -0,0 +1,470 @@
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::path::PathBuf;
+use once_cell::sync::Lazy;
+use regex::Regex;
+use serde_json::{json, Value};
+use crate::minecraft::version::options::QuickPlay;
+use crate::utils::system::{Architecture, ARCHITECTURE, OS};
+
+/// JVM arguments the official launcher adds for versions without `arguments.jvm` (before 1.13)
+static LEGACY_JVM_ARGUMENTS: Lazy<Value> = Lazy::new(|| json!([
+    {
+        "rules": [{ "action": "allow", "os": { "name": "windows" } }],
+        "value": "-XX:HeapDumpPath=MojangTricksIntelFriendlyDriverDoesntCrash.dmp"
+    },
+    {
+        "rules": [{ "action": "allow", "os": { "name": "windows", "version": "^10\\." } }],
+        "value": ["-Dos.name=Windows 10", "-Dos.version=10.0"]
+    },
+    {
+        "rules": [{ "action": "allow", "os": { "arch": "x86" } }],
+        "value": "-Xss1M"
+    },
+    "-Djava.library.path=${natives_directory}",
+    "-Dminecraft.launcher.brand=${launcher_name}",
+    "-Dminecraft.launcher.version=${launcher_version}",
+    "-cp",
+    "${classpath}"
+]));
+
+/// Errors raised while building the command line from a version JSON
+#[derive(Debug)]
+pub enum ArgumentError {
+    /// The version JSON uses a `${...}` placeholder the launcher does not know
+    UnknownPlaceholder { name: String, argument: String },
+    /// The placeholder is known but no value was provided for this launch
+    MissingValue { name: String, argument: String },
+    /// An argument entry is neither a string nor a `{ rules, value }` object
+    InvalidArgument(String),
+    /// The version has no argument for a requested feature, e.g. Quick Play singleplayer before 1.20
+    UnsupportedFeature(String),
+}
+
+impl Display for ArgumentError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ArgumentError::UnknownPlaceholder { name, argument } => write!(f, "Unknown placeholder ${{{}}} in argument '{}'", name, argument),
+            ArgumentError::MissingValue { name, argument } => write!(f, "No value for ${{{}}} in argument '{}'", name, argument),
+            ArgumentError::InvalidArgument(argument) => write!(f, "Invalid argument in version JSON: {}", argument),
+            ArgumentError::UnsupportedFeature(feature) => write!(f, "This version does not support {}", feature),
+        }
+    }
+}
+
+impl Error for ArgumentError {}
+
+/// Values substituted in the `${...}` placeholders of the version JSON
+#[derive(Debug, Clone, Default)]
+pub struct ArgumentContext {
+    pub auth_player_name: String,
+    pub auth_uuid: String,
+    pub auth_access_token: String,
+    pub auth_xuid: String,
+    pub clientid: String,
+    pub user_type: String,
+    pub user_properties: String,
+    pub version_name: String,
+    pub version_type: String,
+    pub game_directory: PathBuf,
+    pub assets_root: PathBuf,
+    pub assets_index_name: String,
+    /// Assets as legacy versions read them: `assets/virtual/legacy`, `resources` or the assets root
+    pub game_assets: PathBuf,
+    pub natives_directory: PathBuf,
+    pub library_directory: PathBuf,
+    pub classpath: String,
+    pub classpath_separator: String,
+    pub launcher_name: String,
+    pub launcher_version: String,
+    pub resolution_width: Option<u32>,
+    pub resolution_height: Option<u32>,
+    pub quick_play_path: Option<PathBuf>,
+    pub quick_play_singleplayer: Option<String>,
+    pub quick_play_multiplayer: Option<String>,
+    pub quick_play_realms: Option<String>,
+    /// Features checked by the argument rules, e.g. `is_demo_user` or `has_custom_resolution`
+    pub features: BTreeMap<String, bool>,
+}
+
+impl ArgumentContext {
+    /// Value of a placeholder, `Ok(None)` when it is known but has no value for this launch
+    fn resolve(&self, name: &str) -> Result<Option<String>, ()> {
+        let path = |path: &PathBuf| Some(path.to_string_lossy().to_string());
+        Ok(match name {
+            "auth_player_name" => Some(self.auth_player_name.clone()),
+            "auth_uuid" => Some(self.auth_uuid.clone()),
+            "auth_access_token" => Some(self.auth_access_token.clone()),
+            "auth_xuid" => Some(self.auth_xuid.clone()),
+            "clientid" => Some(self.clientid.clone()),
+            "user_type" => Some(self.user_type.clone()),
+            "user_properties" => Some(self.user_properties.clone()),
+            "version_name" => Some(self.version_name.clone()),
+            "version_type" => Some(self.version_type.clone()),
+            "game_directory" => path(&self.game_directory),
+            "assets_root" => path(&self.assets_root),
+            "assets_index_name" => Some(self.assets_index_name.clone()),
+            "game_assets" => path(&self.game_assets),
+            // Session format of 1.6, before `--uuid` and `--accessToken` existed
+            "auth_session" => Some(format!("token:{}:{}", self.auth_access_token, self.auth_uuid)),
+            "natives_directory" => path(&self.natives_directory),
+            "library_directory" => path(&self.library_directory),
+            "classpath" => Some(self.classpath.clone()),
+            "classpath_separator" => Some(self.classpath_separator.clone()),
+            "launcher_name" => Some(self.launcher_name.clone()),
+            "launcher_version" => Some(self.launcher_version.clone()),
+            "resolution_width" => self.resolution_width.map(|width| width.to_string()),
+            "resolution_height" => self.resolution_height.map(|height| height.to_string()),
+            "quickPlayPath" => self.quick_play_path.as_ref().and_then(path),
+            "quickPlaySingleplayer" => self.quick_play_singleplayer.clone(),
+            "quickPlayMultiplayer" => self.quick_play_multiplayer.clone(),
+            "quickPlayRealms" => self.quick_play_realms.clone(),
+            _ => return Err(()),
+        })
+    }
+
+    fn has_feature(&self, feature: &str) -> bool {
+        self.features.get(feature).copied().unwrap_or(false)
+    }
+
+    /// Replace every `${...}` placeholder of `argument`
+    pub fn substitute(&self, argument: &str) -> Result<String, ArgumentError> {
+        let mut result = String::with_capacity(argument.len());
+        let mut rest = argument;
+
+        while let Some(start) = rest.find("${") {
+            let end = rest[start..]
+                .find('}')
+                .ok_or_else(|| ArgumentError::InvalidArgument(argument.to_string()))?;
+            let name = &rest[start + 2..start + end];
+
+            let value = match self.resolve(name) {
+                Ok(Some(value)) => value,
+                Ok(None) => return Err(ArgumentError::MissingValue { name: name.to_string(), argument: argument.to_string() }),
+                Err(()) => return Err(ArgumentError::UnknownPlaceholder { name: name.to_string(), argument: argument.to_string() }),
+            };
+            result.push_str(&rest[..start]);
+            result.push_str(&value);
+            rest = &rest[start + end + 1..];
+        }
+        result.push_str(rest);
+        Ok(result)
+    }
+}
+
+/// Whether the version JSON uses the flat `minecraftArguments` string of versions before 1.13
+pub fn is_legacy_format(version_data: &Value) -> bool {
+    version_data["arguments"].is_null() && version_data["minecraftArguments"].is_string()
+}
+
+/// Whether an argument of `version_data` is enabled by `feature`
+pub fn has_feature_argument(version_data: &Value, feature: &str) -> bool {
+    ["game", "jvm"].iter().any(|kind| {
+        version_data["arguments"][kind].as_array().is_some_and(|arguments| {
+            arguments.iter().any(|argument| {
+                argument["rules"].as_array().is_some_and(|rules| {
+                    rules.iter().any(|rule| rule["features"].get(feature).is_some())
+                })
+            })
+        })
+    })
+}
+
+/// Enable `quick_play` in `context`, returns the game arguments to add when the version JSON has no Quick Play.
+///
+/// Versions advertising the `is_quick_play_*` features get their `--quickPlay*` arguments from the version JSON,
+/// older ones can only join a server, with `--server` and `--port`.
+pub fn apply_quick_play(version_data: &Value, quick_play: &QuickPlay, context: &mut ArgumentContext) -> Result<Vec<String>, Box<dyn Error + Send + Sync>> {
+    let feature = quick_play.get_feature();
+    if has_feature_argument(version_data, feature) {
+        match quick_play {
+            QuickPlay::Multiplayer { address } => context.quick_play_multiplayer = Some(address.clone()),
+            QuickPlay::Singleplayer { world } => context.quick_play_singleplayer = Some(world.clone()),
+            QuickPlay::Realms { realm } => context.quick_play_realms = Some(realm.clone()),
+        }
+        context.features.insert(feature.to_string(), true);
+        return Ok(Vec::new());
+    }
+
+    match quick_play.get_server() {
+        Some(server) => {
+            let (host, port) = server?;
+            Ok(vec!["--server".to_string(), host, "--port".to_string(), port.to_string()])
+        }
+        None => Err(ArgumentError::UnsupportedFeature(feature.to_string()).into()),
+    }
+}
+
+/// JVM arguments of `version_data`, substituted, from the `arguments.jvm` array
+pub fn get_jvm_arguments(version_data: &Value, context: &ArgumentContext) -> Result<Vec<String>, ArgumentError> {
+    match version_data["arguments"]["jvm"].as_array() {
+        Some(arguments) => build_arguments(arguments, context),
+        // Versions before 1.13 only list game arguments, the launcher adds the JVM ones
+        None => build_arguments(LEGACY_JVM_ARGUMENTS.as_array().unwrap(), context),
+    }
+}
+
+/// Game arguments of `version_data`, substituted, from the `arguments.game` array
+pub fn get_game_arguments(version_data: &Value, context: &ArgumentContext) -> Result<Vec<String>, ArgumentError> {
+    if let Some(arguments) = version_data["arguments"]["game"].as_array() {
+        return build_arguments(arguments, context);
+    }
+
+    let legacy = version_data["minecraftArguments"]
+        .as_str()
+        .ok_or_else(|| ArgumentError::InvalidArgument("no arguments nor minecraftArguments".to_string()))?;
+    // Tokenize before substituting: values such as the game directory may contain spaces
+    let mut arguments = legacy
+        .split_whitespace()
+        .map(|argument| context.substitute(argument))
+        .collect::<Result<Vec<_>, _>>()?;
+    // Legacy versions have no features, but already know `--demo`, `--width` and `--height`
+    if context.has_feature("is_demo_user") {
+        arguments.push("--demo".to_string());
+    }
+    if context.has_feature("has_custom_resolution")
+        && let (Some(width), Some(height)) = (context.resolution_width, context.resolution_height)
+    {
+        arguments.extend(["--width".to_string(), width.to_string(), "--height".to_string(), height.to_string()]);
+    }
+    Ok(arguments)
+}
+
+fn build_arguments(arguments: &[Value], context: &ArgumentContext) -> Result<Vec<String>, ArgumentError> {
+    let mut result = Vec::new();
+    for argument in arguments {
+        match argument {
+            Value::String(value) => result.push(context.substitute(value)?),
+            Value::Object(object) => {
+                let rules = object.get("rules").and_then(Value::as_array).map(Vec::as_slice).unwrap_or_default();
+                if !rules_allow(rules, context) {
+                    continue;
+                }
+                match object.get("value") {
+                    Some(Value::String(value)) => result.push(context.substitute(value)?),
+                    Some(Value::Array(values)) => {
+                        for value in values {
+                            let value = value.as_str().ok_or_else(|| ArgumentError::InvalidArgument(value.to_string()))?;
+                            result.push(context.substitute(value)?);
+                        }
+                    }
+                    _ => return Err(ArgumentError::InvalidArgument(argument.to_string())),
+                }
+            }
+            _ => return Err(ArgumentError::InvalidArgument(argument.to_string())),
+        }
+    }
+    Ok(result)
+}
+
+/// Evaluate `rules` the way the official launcher does: disallowed unless the last matching rule allows it
+pub(crate) fn rules_allow(rules: &[Value], context: &ArgumentContext) -> bool {
+    if rules.is_empty() {
+        return true;
+    }
+
+    let mut allowed = false;
+    for rule in rules {
+        if rule_matches(rule, context) {
+            allowed = rule["action"] == "allow";
+        }
+    }
+    allowed
+}
+
+fn rule_matches(rule: &Value, context: &ArgumentContext) -> bool {
+    if let Some(os) = rule["os"].as_object() {
+        if let Some(name) = os.get("name").and_then(Value::as_str)
+            && OS.get_simple_name().ok() != Some(name)
+        {
+            return false;
+        }
+        if let Some(arch) = os.get("arch").and_then(Value::as_str) {
+            // Only "x86" is used, for 32 bits JVMs
+            let is_x86 = ARCHITECTURE == Architecture::X86;
+            if (arch == "x86") != is_x86 {
+                return false;
+            }
+        }
+        if let Some(version) = os.get("version").and_then(Value::as_str)
+            && !version_matches(version, &os_info::get().version().to_string())
+        {
+            return false;
+        }
+    }
+
+    if let Some(features) = rule["features"].as_object() {
+        return features
+            .iter()
+            .all(|(feature, expected)| expected.as_bool() == Some(context.has_feature(feature)));
+    }
+    true
+}
+
+/// OS versions are matched against a regex, e.g. `^10\\.5\\.\\d$`. An invalid one matches nothing.
+fn version_matches(pattern: &str, version: &str) -> bool {
+    Regex::new(pattern).is_ok_and(|regex| regex.is_match(version))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn context() -> ArgumentContext {
+        ArgumentContext {
+            auth_player_name: "Hamadi".to_string(),
+            version_name: "1.20.2".to_string(),
+            version_type: "release".to_string(),
+            assets_index_name: "8".to_string(),
+            natives_directory: PathBuf::from("/game/natives"),
+            classpath: "a.jar:b.jar".to_string(),
+            launcher_name: "LightyLauncher".to_string(),
+            ..ArgumentContext::default()
+        }
+    }
+
+    fn version_data() -> Value {
+        let os = OS.get_simple_name().unwrap();
+        json!({
+            "arguments": {
+                "game": [
+                    "--username", "${auth_player_name}",
+                    "--assetIndex", "${assets_index_name}",
+                    { "rules": [{ "action": "allow", "features": { "is_demo_user": true } }], "value": "--demo" },
+                    {
+                        "rules": [{ "action": "allow", "features": { "has_custom_resolution": true } }],
+                        "value": ["--width", "${resolution_width}", "--height", "${resolution_height}"]
+                    }
+                ],
+                "jvm": [
+                    { "rules": [{ "action": "allow", "os": { "name": os } }], "value": "-Dcurrent.os=true" },
+                    { "rules": [{ "action": "allow", "os": { "name": "not-an-os" } }], "value": "-Dother.os=true" },
+                    "-Djava.library.path=${natives_directory}",
+                    "-Dminecraft.launcher.brand=${launcher_name}",
+                    "-cp", "${classpath}"
+                ]
+            }
+        })
+    }
+
+    #[test]
+    fn rules_and_placeholders_are_applied() {
+        let jvm = get_jvm_arguments(&version_data(), &context()).unwrap();
+        assert_eq!(jvm, vec![
+            "-Dcurrent.os=true",
+            "-Djava.library.path=/game/natives",
+            "-Dminecraft.launcher.brand=LightyLauncher",
+            "-cp",
+            "a.jar:b.jar",
+        ]);
+
+        let game = get_game_arguments(&version_data(), &context()).unwrap();
+        assert_eq!(game, vec!["--username", "Hamadi", "--assetIndex", "8"]);
+    }
+
+    #[test]
+    fn features_enable_arguments() {
+        let mut context = context();
+        context.features.insert("is_demo_user".to_string(), true);
+        context.features.insert("has_custom_resolution".to_string(), true);
+        context.resolution_width = Some(1280);
+        context.resolution_height = Some(720);
+
+        let game = get_game_arguments(&version_data(), &context).unwrap();
+        assert_eq!(&game[4..], ["--demo", "--width", "1280", "--height", "720"]);
+    }
+
+    #[test]
+    fn quick_play_uses_the_version_arguments_when_advertised() {
+        let version_1_20 = json!({
+            "arguments": {
+                "game": [
+                    "--username", "${auth_player_name}",
+                    { "rules": [{ "action": "allow", "features": { "is_quick_play_singleplayer": true } }], "value": ["--quickPlaySingleplayer", "${quickPlaySingleplayer}"] },
+                    { "rules": [{ "action": "allow", "features": { "is_quick_play_multiplayer": true } }], "value": ["--quickPlayMultiplayer", "${quickPlayMultiplayer}"] }
+                ]
+            }
+        });
+        let server = QuickPlay::Multiplayer { address: "play.example.com:25566".to_string() };
+
+        let mut server_context = context();
+        let extra = apply_quick_play(&version_1_20, &server, &mut server_context).unwrap();
+        assert!(extra.is_empty());
+        let game = get_game_arguments(&version_1_20, &server_context).unwrap();
+        assert_eq!(&game[2..], ["--quickPlayMultiplayer", "play.example.com:25566"]);
+
+        let mut world_context = context();
+        let world = QuickPlay::Singleplayer { world: "My World".to_string() };
+        apply_quick_play(&version_1_20, &world, &mut world_context).unwrap();
+        let game = get_game_arguments(&version_1_20, &world_context).unwrap();
+        assert_eq!(&game[2..], ["--quickPlaySingleplayer", "My World"]);
+
+        // Before Quick Play, only servers can be joined
+        let mut legacy_context = context();
+        let extra = apply_quick_play(&version_data(), &server, &mut legacy_context).unwrap();
+        assert_eq!(extra, vec!["--server", "play.example.com", "--port", "25566"]);
+        assert!(apply_quick_play(&version_data(), &world, &mut legacy_context).is_err());
+    }
+
+    #[test]
+    fn legacy_arguments_are_tokenized_and_substituted() {
+        let mut context = context();
+        context.game_directory = PathBuf::from("/games/my instance");
+        context.game_assets = PathBuf::from("/games/my instance/resources");
+        context.auth_access_token = "token".to_string();
+        context.auth_uuid = "uuid".to_string();
+        context.user_type = "legacy".to_string();
+
+        let version_1_6 = json!({
+            "minecraftArguments": "--username ${auth_player_name} --session ${auth_session} --gameDir ${game_directory} --assetsDir ${game_assets}"
+        });
+        assert!(is_legacy_format(&version_1_6));
+        let game = get_game_arguments(&version_1_6, &context).unwrap();
+        assert_eq!(game, vec![
+            "--username", "Hamadi",
+            "--session", "token:token:uuid",
+            "--gameDir", "/games/my instance",
+            "--assetsDir", "/games/my instance/resources",
+        ]);
+
+        let jvm = get_jvm_arguments(&version_1_6, &context).unwrap();
+        assert!(jvm.contains(&"-Djava.library.path=/game/natives".to_string()));
+        assert!(jvm.contains(&"-Dminecraft.launcher.brand=LightyLauncher".to_string()));
+        assert_eq!(&jvm[jvm.len() - 2..], ["-cp", "a.jar:b.jar"]);
+
+        context.features.insert("is_demo_user".to_string(), true);
+        let forge_1_7 = json!({
+            "minecraftArguments": "--userType ${user_type} --tweakClass cpw.mods.fml.common.launcher.FMLTweaker"
+        });
+        let game = get_game_arguments(&forge_1_7, &context).unwrap();
+        assert_eq!(game, vec!["--userType", "legacy", "--tweakClass", "cpw.mods.fml.common.launcher.FMLTweaker", "--demo"]);
+
+        context.features.insert("has_custom_resolution".to_string(), true);
+        context.resolution_width = Some(854);
+        context.resolution_height = Some(480);
+        let game = get_game_arguments(&forge_1_7, &context).unwrap();
+        assert_eq!(&game[game.len() - 4..], ["--width", "854", "--height", "480"]);
+    }
+
+    #[test]
+    fn unknown_placeholders_are_errors() {
+        let error = context().substitute("--foo=${not_a_placeholder}").unwrap_err();
+        assert!(matches!(error, ArgumentError::UnknownPlaceholder { ref name, .. } if name == "not_a_placeholder"));
+
+        let mut context = context();
+        context.features.insert("has_custom_resolution".to_string(), true);
+        let error = get_game_arguments(&version_data(), &context).unwrap_err();
+        assert!(matches!(error, ArgumentError::MissingValue { ref name, .. } if name == "resolution_width"));
+    }
+
+    #[test]
+    fn os_versions_are_matched_as_regexes() {
+        assert!(version_matches("^10\\.5\\.\\d$", "10.5.8"));
+        assert!(!version_matches("^10\\.5\\.\\d$", "10.6.1"));
+        assert!(!version_matches("^10\\.5\\.\\d$", "10.5.12"));
+        assert!(version_matches("^6\\.", "6.18.44"));
+        assert!(!version_matches("^10\\.(", "10.5.8"));
+    }
+}
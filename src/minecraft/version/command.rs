@@ -0,0 +1,169 @@
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::path::{Path, PathBuf};
+use serde::Serialize;
+use tokio::fs;
+
+/// Stands for the access token in exported commands, the launch script reads it from the environment
+pub const ACCESS_TOKEN_PLACEHOLDER: &str = "${ACCESS_TOKEN}";
+
+/// Everything [`super::launch::Launch::launch`] resolves before starting Java
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct LaunchCommand {
+    pub java: PathBuf,
+    /// Commands Java is started through, the first one is the outermost
+    pub wrappers: Vec<Vec<String>>,
+    /// JVM arguments, the classpath included
+    pub jvm_arguments: Vec<String>,
+    pub main_class: String,
+    pub game_arguments: Vec<String>,
+    pub classpath: Vec<PathBuf>,
+    pub working_directory: PathBuf,
+    /// Variables set on top of the launcher environment
+    pub environment: BTreeMap<String, String>,
+    /// Variables of the launcher environment the game does not inherit
+    pub removed_environment: Vec<String>,
+    /// Game arguments with [`ACCESS_TOKEN_PLACEHOLDER`] instead of the access token
+    #[serde(skip)]
+    pub(crate) redacted_game_arguments: Vec<String>,
+}
+
+impl LaunchCommand {
+    /// Arguments of Java: JVM arguments, main class then game arguments
+    pub fn get_arguments(&self) -> Vec<String> {
+        let mut arguments = self.jvm_arguments.clone();
+        arguments.push(self.main_class.clone());
+        arguments.extend(self.game_arguments.iter().cloned());
+        arguments
+    }
+
+    /// Wrappers, Java and its arguments, as they are executed
+    pub fn get_command_line(&self) -> Vec<String> {
+        let mut command_line: Vec<String> = self.wrappers.iter().flatten().cloned().collect();
+        command_line.push(self.java.to_string_lossy().to_string());
+        command_line.extend(self.get_arguments());
+        command_line
+    }
+
+    /// The same command with [`ACCESS_TOKEN_PLACEHOLDER`] instead of the access token
+    pub fn redacted(&self) -> Self {
+        Self {
+            game_arguments: self.redacted_game_arguments.clone(),
+            ..self.clone()
+        }
+    }
+
+    /// JSON of the command, the access token is only included if `include_access_token` is set
+    pub fn to_json(&self, include_access_token: bool) -> Result<String, serde_json::Error> {
+        match include_access_token {
+            true => serde_json::to_string_pretty(self),
+            false => serde_json::to_string_pretty(&self.redacted()),
+        }
+    }
+
+    /// Standalone `sh` script starting the game.
+    ///
+    /// Without `include_access_token` the script reads the token from `ACCESS_TOKEN` and refuses to run without it.
+    pub fn to_shell_script(&self, include_access_token: bool) -> String {
+        let command = match include_access_token {
+            true => self.clone(),
+            false => self.redacted(),
+        };
+
+        let mut script = String::from("#!/bin/sh\n");
+        script.push_str(&format!("# Generated by {} {}\n", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION")));
+        if !include_access_token {
+            script.push_str(": \"${ACCESS_TOKEN:?Set ACCESS_TOKEN to the access token of the account}\"\n");
+        }
+        script.push_str(&format!("cd {} || exit 1\n", quote(&command.working_directory.to_string_lossy())));
+        for name in &command.removed_environment {
+            script.push_str(&format!("unset {}\n", name));
+        }
+        for (name, value) in &command.environment {
+            script.push_str(&format!("export {}={}\n", name, quote(value)));
+        }
+        let command_line: Vec<String> = command.get_command_line().iter().map(|argument| quote(argument)).collect();
+        script.push_str(&format!("exec {}\n", command_line.join(" ")));
+        script
+    }
+
+    /// Write [`Self::to_shell_script`] to `path`, executable on Unix
+    pub async fn write_shell_script(&self, path: &Path, include_access_token: bool) -> Result<(), Box<dyn Error + Send + Sync>> {
+        fs::write(path, self.to_shell_script(include_access_token)).await?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(path, std::fs::Permissions::from_mode(0o755)).await?;
+        }
+        Ok(())
+    }
+}
+
+/// Quote `argument` for `sh`, the token placeholder is left to the shell
+fn quote(argument: &str) -> String {
+    if !argument.contains(ACCESS_TOKEN_PLACEHOLDER) {
+        return shell_words::quote(argument).to_string();
+    }
+    argument
+        .split(ACCESS_TOKEN_PLACEHOLDER)
+        .map(|part| match part.is_empty() {
+            true => String::new(),
+            false => shell_words::quote(part).to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join("\"$ACCESS_TOKEN\"")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn command() -> LaunchCommand {
+        LaunchCommand {
+            java: PathBuf::from("/jre/bin/java"),
+            wrappers: vec![vec!["gamemoderun".to_string()]],
+            jvm_arguments: vec!["-Xmx2048M".to_string(), "-cp".to_string(), "/game/a.jar:/game/my pack.jar".to_string()],
+            main_class: "net.minecraft.client.main.Main".to_string(),
+            game_arguments: vec!["--accessToken".to_string(), "secret-token".to_string(), "--session".to_string(), "token:secret-token:uuid".to_string()],
+            classpath: vec![PathBuf::from("/game/a.jar"), PathBuf::from("/game/my pack.jar")],
+            working_directory: PathBuf::from("/game"),
+            environment: BTreeMap::from([("MESA_GL_VERSION_OVERRIDE".to_string(), "4.5".to_string())]),
+            removed_environment: vec!["JAVA_TOOL_OPTIONS".to_string()],
+            redacted_game_arguments: vec![
+                "--accessToken".to_string(),
+                ACCESS_TOKEN_PLACEHOLDER.to_string(),
+                "--session".to_string(),
+                format!("token:{}:uuid", ACCESS_TOKEN_PLACEHOLDER),
+            ],
+        }
+    }
+
+    #[test]
+    fn access_token_is_redacted_unless_requested() {
+        let json = command().to_json(false).unwrap();
+        assert!(!json.contains("secret-token"));
+        assert!(json.contains("\"main_class\": \"net.minecraft.client.main.Main\""));
+        assert!(!json.contains("redacted_game_arguments"));
+        assert!(command().to_json(true).unwrap().contains("secret-token"));
+
+        assert_eq!(command().get_command_line()[..3], ["gamemoderun", "/jre/bin/java", "-Xmx2048M"]);
+    }
+
+    #[test]
+    fn shell_script_is_quoted() {
+        let script = command().to_shell_script(false);
+        assert!(script.starts_with("#!/bin/sh\n"));
+        assert!(script.contains(": \"${ACCESS_TOKEN:?"));
+        assert!(script.contains("cd /game || exit 1\n"));
+        assert!(script.contains("unset JAVA_TOOL_OPTIONS\n"));
+        assert!(script.contains("export MESA_GL_VERSION_OVERRIDE=4.5\n"));
+        assert!(script.ends_with(
+            "exec gamemoderun /jre/bin/java -Xmx2048M -cp '/game/a.jar:/game/my pack.jar' net.minecraft.client.main.Main \
+            --accessToken \"$ACCESS_TOKEN\" --session token:\"$ACCESS_TOKEN\":uuid\n"
+        ));
+
+        let script = command().to_shell_script(true);
+        assert!(script.contains("--accessToken secret-token"));
+        assert!(!script.contains("ACCESS_TOKEN"));
+    }
+}
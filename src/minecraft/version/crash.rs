@@ -0,0 +1,293 @@
+use std::path::{Path, PathBuf};
+use std::process::ExitStatus;
+use std::time::SystemTime;
+use tokio::fs;
+use crate::minecraft::version::mappings::ProguardMappings;
+
+/// Most likely reason of a crash, from known messages of the JVM, the game and the loaders
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CrashCause {
+    /// The game or a mod needs another Java version
+    WrongJavaVersion,
+    /// The heap or the system memory is exhausted
+    OutOfMemory,
+    /// A mod needs a mod that is not installed, or another version of it
+    MissingModDependency,
+    /// A mixin could not be applied, usually two incompatible mods
+    MixinFailure,
+    /// OpenGL could not be initialized or the driver crashed
+    GraphicsDriver,
+    Unknown,
+}
+
+impl CrashCause {
+    /// Message to show to the player
+    pub fn get_description(&self) -> &'static str {
+        match self {
+            CrashCause::WrongJavaVersion => "The game was started with an incompatible Java version",
+            CrashCause::OutOfMemory => "The game ran out of memory, try allocating more",
+            CrashCause::MissingModDependency => "A mod is missing one of its dependencies",
+            CrashCause::MixinFailure => "A mod failed to patch the game, it may be incompatible with another mod",
+            CrashCause::GraphicsDriver => "The graphics driver failed, try updating it",
+            CrashCause::Unknown => "The game crashed",
+        }
+    }
+}
+
+/// What is known about a game that did not exit cleanly
+#[derive(Debug, Clone)]
+pub struct CrashReport {
+    pub exit_status: ExitStatus,
+    pub cause: CrashCause,
+    /// `crash-reports/crash-*.txt` written during this session
+    pub report_path: Option<PathBuf>,
+    /// `hs_err_pid*.log` written by the JVM during this session
+    pub jvm_error_path: Option<PathBuf>,
+    /// `Description:` of the crash report
+    pub description: Option<String>,
+    pub stack_trace: Option<String>,
+    /// Mods listed in the system details of the crash report
+    pub mods: Vec<String>,
+    /// Last lines of the game output
+    pub log_tail: Vec<String>,
+}
+
+impl CrashReport {
+    /// Rewrite the stack trace and the log tail with the Mojang names of the client mappings
+    pub fn deobfuscate(&mut self, mappings: &ProguardMappings) {
+        if let Some(stack_trace) = &self.stack_trace {
+            self.stack_trace = Some(mappings.deobfuscate(stack_trace));
+        }
+        self.log_tail = self.log_tail.iter().map(|line| mappings.deobfuscate(line)).collect();
+    }
+}
+
+/// Sections of a `---- Minecraft Crash Report ----` file
+#[derive(Debug, Default, PartialEq, Eq)]
+pub(crate) struct CrashReportFile {
+    pub(crate) description: Option<String>,
+    pub(crate) stack_trace: Option<String>,
+    pub(crate) mods: Vec<String>,
+}
+
+/// Look for the traces of a crash in `game_dir` after the game exited, `None` if it exited cleanly
+pub async fn analyze_exit(game_dir: &Path, started_at: SystemTime, exit_status: ExitStatus, log_tail: Vec<String>) -> Option<CrashReport> {
+    let report_path = find_newest(&game_dir.join("crash-reports"), started_at, |name| name.ends_with(".txt")).await;
+    let jvm_error_path = find_newest(game_dir, started_at, |name| name.starts_with("hs_err_pid") && name.ends_with(".log")).await;
+    if exit_status.success() && report_path.is_none() {
+        return None;
+    }
+
+    let report = match &report_path {
+        Some(path) => fs::read_to_string(path).await.ok(),
+        None => None,
+    };
+    let jvm_error = match &jvm_error_path {
+        Some(path) => fs::read_to_string(path).await.ok(),
+        None => None,
+    };
+    let file = report.as_deref().map(parse_crash_report).unwrap_or_default();
+
+    let mut evidence = log_tail.join("\n");
+    for text in [&report, &jvm_error].into_iter().flatten() {
+        evidence.push('\n');
+        evidence.push_str(text);
+    }
+
+    Some(CrashReport {
+        exit_status,
+        cause: classify(&evidence),
+        report_path,
+        jvm_error_path,
+        description: file.description,
+        stack_trace: file.stack_trace,
+        mods: file.mods,
+        log_tail,
+    })
+}
+
+/// Newest file of `dir` accepted by `filter` modified since `since`
+async fn find_newest(dir: &Path, since: SystemTime, filter: impl Fn(&str) -> bool) -> Option<PathBuf> {
+    let mut entries = fs::read_dir(dir).await.ok()?;
+    let mut newest: Option<(SystemTime, PathBuf)> = None;
+    while let Ok(Some(entry)) = entries.next_entry().await {
+        if !filter(&entry.file_name().to_string_lossy()) {
+            continue;
+        }
+        let Ok(modified) = entry.metadata().await.and_then(|metadata| metadata.modified()) else {
+            continue;
+        };
+        if modified >= since && newest.as_ref().is_none_or(|(time, _)| modified > *time) {
+            newest = Some((modified, entry.path()));
+        }
+    }
+    newest.map(|(_, path)| path)
+}
+
+pub(crate) fn parse_crash_report(report: &str) -> CrashReportFile {
+    let mut file = CrashReportFile::default();
+    let lines: Vec<&str> = report.lines().collect();
+
+    if let Some(index) = lines.iter().position(|line| line.starts_with("Description: ")) {
+        file.description = Some(lines[index]["Description: ".len()..].trim().to_string());
+        // The stack trace follows the description, up to the detailed walkthrough
+        let trace: Vec<&str> = lines[index + 1..]
+            .iter()
+            .skip_while(|line| line.trim().is_empty())
+            .take_while(|line| !line.trim().is_empty() && !line.starts_with("A detailed walkthrough"))
+            .copied()
+            .collect();
+        if !trace.is_empty() {
+            file.stack_trace = Some(trace.join("\n"));
+        }
+    }
+
+    // `Fabric Mods:`, `Mod List:` (Forge) or `Loaded mods:`, followed by more indented lines
+    let mut lines = lines.iter().peekable();
+    while let Some(line) = lines.next() {
+        let key = line.trim();
+        if !(key.ends_with("Mods:") || key == "Mod List:" || key == "Loaded mods:") {
+            continue;
+        }
+        let indent = line.len() - line.trim_start().len();
+        while let Some(next) = lines.peek() {
+            if next.trim().is_empty() || next.len() - next.trim_start().len() <= indent {
+                break;
+            }
+            file.mods.push(next.trim().to_string());
+            lines.next();
+        }
+    }
+    file
+}
+
+/// Find the cause of a crash in the log, crash report and JVM error file
+pub(crate) fn classify(evidence: &str) -> CrashCause {
+    let contains_any = |patterns: &[&str]| patterns.iter().any(|pattern| evidence.contains(pattern));
+
+    if contains_any(&[
+        "java.lang.UnsupportedClassVersionError",
+        "has been compiled by a more recent version of the Java Runtime",
+        "Unsupported class file major version",
+        // Forge before 1.13 on Java 9+
+        "cannot be cast to class java.net.URLClassLoader",
+        // Fabric: "requires version 17 or later of java, which is missing!"
+        "of java, which is missing",
+    ]) {
+        return CrashCause::WrongJavaVersion;
+    }
+    if contains_any(&[
+        "java.lang.OutOfMemoryError",
+        "There is insufficient memory for the Java Runtime Environment",
+        "Could not reserve enough space for object heap",
+    ]) {
+        return CrashCause::OutOfMemory;
+    }
+    if contains_any(&[
+        "Missing or unsupported mandatory dependencies",
+        "Mod resolution encountered an incompatible mod set",
+        "requires any version of",
+        "Could not find required mod:",
+        "Missing Mods:",
+    ]) {
+        return CrashCause::MissingModDependency;
+    }
+    if contains_any(&[
+        "org.spongepowered.asm.mixin.transformer.throwables.MixinTransformerError",
+        "org.spongepowered.asm.mixin.throwables.MixinApplyError",
+        "org.spongepowered.asm.mixin.injection.throwables.InvalidInjectionException",
+        "Mixin apply failed",
+        "Mixin apply for mod",
+    ]) {
+        return CrashCause::MixinFailure;
+    }
+    if contains_any(&[
+        "Pixel format not accelerated",
+        "GLFW error 65542",
+        "GLFW error 65543",
+        "The driver does not appear to support OpenGL",
+        "No OpenGL context found in the current thread",
+        "Couldn't set pixel format",
+        // JVM crashes in the OpenGL driver of AMD, Intel and NVIDIA
+        "atio6axx.dll",
+        "ig9icd64.dll",
+        "ig7icd64.dll",
+        "nvoglv64.dll",
+    ]) {
+        return CrashCause::GraphicsDriver;
+    }
+    CrashCause::Unknown
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const REPORT: &str = "---- Minecraft Crash Report ----
+// Why did you do that?
+
+Time: 2024-05-01 12:00:00
+Description: Initializing game
+
+java.lang.RuntimeException: Mixin transformation of net.minecraft.class_310 failed
+\tat net.fabricmc.loader.impl.launch.knot.KnotClassDelegate.getPostMixinClassByteArray(KnotClassDelegate.java:427)
+Caused by: org.spongepowered.asm.mixin.transformer.throwables.MixinTransformerError: An unexpected critical error was encountered
+\t... 3 more
+
+A detailed walkthrough of the error, its code path and all known details is as follows:
+---------------------------------------------------------------------------------------
+
+-- System Details --
+Details:
+\tMinecraft Version: 1.20.1
+\tFabric Mods:
+\t\tfabric-api: Fabric API 0.90.0+1.20.1
+\t\tsodium: Sodium 0.5.3
+\tLaunched Version: fabric-loader-0.15.10-1.20.1
+";
+
+    #[test]
+    fn crash_report_sections_are_parsed() {
+        let file = parse_crash_report(REPORT);
+        assert_eq!(file.description.as_deref(), Some("Initializing game"));
+        let stack_trace = file.stack_trace.unwrap();
+        assert!(stack_trace.starts_with("java.lang.RuntimeException: Mixin transformation"));
+        assert!(stack_trace.ends_with("\t... 3 more"));
+        assert_eq!(file.mods, vec!["fabric-api: Fabric API 0.90.0+1.20.1", "sodium: Sodium 0.5.3"]);
+        assert_eq!(classify(REPORT), CrashCause::MixinFailure);
+    }
+
+    #[test]
+    fn common_causes_are_classified() {
+        assert_eq!(
+            classify("Error: LinkageError occurred while loading main class net.minecraft.client.main.Main\n\tjava.lang.UnsupportedClassVersionError: net/minecraft/client/main/Main has been compiled by a more recent version of the Java Runtime (class file version 65.0)"),
+            CrashCause::WrongJavaVersion
+        );
+        assert_eq!(classify("Exception in thread \"Render thread\" java.lang.OutOfMemoryError: Java heap space"), CrashCause::OutOfMemory);
+        assert_eq!(
+            classify("Incompatible mods found!\n - Mod 'Sodium Extra' (sodium-extra) 0.5.1 requires any version of sodium, which is missing!"),
+            CrashCause::MissingModDependency
+        );
+        assert_eq!(classify("[12:00:00] [Render thread/ERROR]: GLFW error 65542: WGL: The driver does not appear to support OpenGL"), CrashCause::GraphicsDriver);
+        assert_eq!(classify("Stopping!"), CrashCause::Unknown);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn clean_exits_are_not_crashes() {
+        use std::os::unix::process::ExitStatusExt;
+
+        let game_dir = tempfile::tempdir().unwrap();
+        let started_at = SystemTime::now();
+        assert!(analyze_exit(game_dir.path(), started_at, ExitStatus::from_raw(0), Vec::new()).await.is_none());
+
+        std::fs::create_dir(game_dir.path().join("crash-reports")).unwrap();
+        std::fs::write(game_dir.path().join("crash-reports").join("crash-2024-05-01_12.00.00-client.txt"), REPORT).unwrap();
+        // Exit code 255, as `System.exit(-1)` reports it
+        let report = analyze_exit(game_dir.path(), started_at, ExitStatus::from_raw(255 << 8), vec!["Stopping!".to_string()]).await.unwrap();
+        assert_eq!(report.exit_status.code(), Some(255));
+        assert_eq!(report.cause, CrashCause::MixinFailure);
+        assert_eq!(report.description.as_deref(), Some("Initializing game"));
+        assert!(report.report_path.unwrap().ends_with("crash-2024-05-01_12.00.00-client.txt"));
+    }
+}
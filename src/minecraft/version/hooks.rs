@@ -0,0 +1,126 @@
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::path::PathBuf;
+use std::process::{ExitStatus, Stdio};
+use std::time::Duration;
+use tokio::process::Command;
+
+/// Time a hook gets before it is killed, the launch waits for the pre-launch one
+pub const DEFAULT_HOOK_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+
+/// Errors raised while running a pre-launch or post-exit hook
+#[derive(Debug)]
+pub enum HookError {
+    /// The shell could not be started
+    Spawn { command: String, error: std::io::Error },
+    /// The command exited with a non-zero status
+    Failed { command: String, output: HookOutput },
+    /// The command was still running after the timeout and was killed
+    TimedOut { command: String, timeout: Duration },
+}
+
+impl Display for HookError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            HookError::Spawn { command, error } => write!(f, "Could not run hook '{}': {}", command, error),
+            HookError::Failed { command, output } => write!(f, "Hook '{}' failed with {}: {}", command, output.status, output.stderr.trim()),
+            HookError::TimedOut { command, timeout } => write!(f, "Hook '{}' did not finish within {}s", command, timeout.as_secs()),
+        }
+    }
+}
+
+impl Error for HookError {}
+
+/// Captured result of a hook
+#[derive(Debug, Clone)]
+pub struct HookOutput {
+    pub status: ExitStatus,
+    pub stdout: String,
+    pub stderr: String,
+}
+
+/// Command run through the shell around the game, with the `INST_*` variables describing the instance
+#[derive(Debug, Clone)]
+pub struct Hook {
+    pub command: String,
+    pub environment: BTreeMap<String, String>,
+    pub directory: PathBuf,
+    /// The command is killed past it
+    pub timeout: Duration,
+}
+
+impl Hook {
+    /// Run the command until it exits or times out, its output is captured
+    pub async fn run(&self) -> Result<HookOutput, HookError> {
+        let mut command = if cfg!(target_os = "windows") {
+            let mut command = Command::new("cmd");
+            command.arg("/C");
+            command
+        } else {
+            let mut command = Command::new("sh");
+            command.arg("-c");
+            command
+        };
+        command
+            .arg(&self.command)
+            .current_dir(&self.directory)
+            .envs(&self.environment)
+            .stdin(Stdio::null())
+            .kill_on_drop(true);
+
+        let output = tokio::time::timeout(self.timeout, command.output())
+            .await
+            .map_err(|_| HookError::TimedOut { command: self.command.clone(), timeout: self.timeout })?
+            .map_err(|error| HookError::Spawn { command: self.command.clone(), error })?;
+        let output = HookOutput {
+            status: output.status,
+            stdout: String::from_utf8_lossy(&output.stdout).to_string(),
+            stderr: String::from_utf8_lossy(&output.stderr).to_string(),
+        };
+        if !output.status.success() {
+            return Err(HookError::Failed { command: self.command.clone(), output });
+        }
+        Ok(output)
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    fn hook(command: &str) -> Hook {
+        Hook {
+            command: command.to_string(),
+            environment: BTreeMap::from([("INST_NAME".to_string(), "minozia".to_string())]),
+            directory: std::env::temp_dir(),
+            timeout: Duration::from_secs(5),
+        }
+    }
+
+    #[tokio::test]
+    async fn hooks_see_the_instance_and_are_captured() {
+        let output = hook("echo \"starting $INST_NAME\"; echo warning >&2").run().await.unwrap();
+        assert_eq!(output.stdout, "starting minozia\n");
+        assert_eq!(output.stderr, "warning\n");
+    }
+
+    #[tokio::test]
+    async fn failing_hooks_are_errors() {
+        let error = hook("echo 'no network' >&2; exit 2").run().await.unwrap_err();
+        let HookError::Failed { output, .. } = error else {
+            panic!("unexpected error: {}", error);
+        };
+        assert_eq!(output.status.code(), Some(2));
+        assert_eq!(output.stderr, "no network\n");
+    }
+
+    #[tokio::test]
+    async fn hung_hooks_time_out() {
+        let hook = Hook { timeout: Duration::from_millis(200), ..hook("sleep 30") };
+        let started = std::time::Instant::now();
+        let error = hook.run().await.unwrap_err();
+        assert!(matches!(error, HookError::TimedOut { .. }));
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+}
@@ -1,14 +1,30 @@
+use std::error::Error;
+use log::{debug, error, info};
 use std::path::{Path, PathBuf};
 use crate::java::{find_java_binary, JavaDistribution, JavaRuntime};
+use crate::minecraft::auth::authlib_injector::AuthlibInjector;
+use crate::minecraft::auth::session::AccountEvent;
+use crate::minecraft::auth::store::AccountStore;
+use crate::minecraft::auth::{UserProfile, UserType};
+use crate::minecraft::version::command::{LaunchCommand, ACCESS_TOKEN_PLACEHOLDER};
+use crate::minecraft::version::hooks::{Hook, DEFAULT_HOOK_TIMEOUT};
+use crate::minecraft::version::lock::{InstanceLockGuard, LockOperation};
+use crate::minecraft::version::process::GameProcess;
+use crate::minecraft::version::sessions::SessionStore;
 use crate::minecraft::version::version::Version;
-use tokio::sync::oneshot;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use crate::minecraft::version::arguments::{apply_quick_play, get_game_arguments, get_jvm_arguments, is_legacy_format, ArgumentContext};
+use crate::minecraft::version::loaders::utils::assets::{get_asset_index_name, Assets};
 use crate::minecraft::version::loaders::utils::librairies::Libraries;
-use crate::minecraft::version::loaders::utils::manifest::Manifest;
-use crate::utils::system::{OperatingSystem, OS};
+use crate::minecraft::version::loaders::utils::logging::{get_log_config, Logging};
+use crate::utils::system::OS;
 
 pub trait Launch<'a> {
     fn get_client_path(&self) -> PathBuf;
-    async fn launch(&self, path: &PathBuf);
+    async fn launch(&self, path: &Path, profile: &UserProfile) -> Result<GameProcess, Box<dyn Error + Send + Sync>>;
+    async fn prepare_launch(&self, path: &Path, profile: &UserProfile) -> Result<LaunchCommand, Box<dyn Error + Send + Sync>>;
+    async fn launch_active_account<F: Fn(&AccountEvent)>(&self, path: &Path, store: &mut AccountStore, on_event: F) -> Result<GameProcess, Box<dyn Error + Send + Sync>>;
 }
 
 impl<'a> Launch<'a> for Version<'a> {
@@ -16,17 +32,90 @@ impl<'a> Launch<'a> for Version<'a> {
         self.get_game_dir().join(format!("{}.jar", self.name))
     }
 
+    /// Check the session of the active account, refreshing it if needed, then launch with it
+    async fn launch_active_account<F: Fn(&AccountEvent)>(&self, path: &Path, store: &mut AccountStore, on_event: F) -> Result<GameProcess, Box<dyn Error + Send + Sync>> {
+        let profile = store.ensure_active_session(on_event).await?;
+        self.launch(path, &profile).await
+    }
 
-    async fn launch(&self, path: &PathBuf) {
+    /// Start the game in the background and return a handle on it, the instance stays locked until it exits and the session is recorded
+    async fn launch(&self, path: &Path, profile: &UserProfile) -> Result<GameProcess, Box<dyn Error + Send + Sync>> {
+        let mut lock = InstanceLockGuard::acquire(&self.get_game_dir(), LockOperation::Running, Some(profile.name.clone())).await?;
+        // `prepare_launch` only reads from disk, instances installed by older launchers miss some of these files
+        self.install_launch_files().await?;
+        if let Some(api_url) = &profile.yggdrasil_server {
+            let injector = AuthlibInjector::new(&path.join("authlib-injector"));
+            injector.install().await?;
+            injector.prefetch(api_url).await?;
+        }
+        let command = self.prepare_launch(path, profile).await?;
+        debug!("Java arguments: {:?}", command.redacted().get_arguments());
+
+        let java_runtime = JavaRuntime::new(command.java.clone());
+        let hook = |hook_command: &String| Hook {
+            command: hook_command.clone(),
+            environment: self.get_hook_environment(&java_runtime),
+            directory: command.working_directory.clone(),
+            timeout: DEFAULT_HOOK_TIMEOUT,
+        };
+        let pre_launch_output = match &self.launch_options.pre_launch_command {
+            Some(hook_command) => Some(hook(hook_command).run().await?),
+            None => None,
+        };
+        let post_exit = self.launch_options.post_exit_command.as_ref().map(hook);
+
+        // Closed before Java starts, the log of the new game says nothing about the sessions left open
+        let sessions = match SessionStore::open(&command.working_directory).await {
+            Ok(mut sessions) => {
+                if let Err(e) = sessions.close_stale().await {
+                    error!("Could not close the previous sessions: {}", e);
+                }
+                Some(sessions)
+            }
+            Err(e) => {
+                error!("Could not read the sessions: {}", e);
+                None
+            }
+        };
+        let child = java_runtime
+            .execute_with(command.get_arguments(), &command.working_directory, &command.wrappers, &command.environment, &command.removed_environment)
+            .await?;
+        if let Some(pid) = child.id() {
+            lock.set_pid(pid).await?;
+        }
+        let session = match sessions {
+            Some(mut sessions) => match sessions.start(Some(profile.name.clone()), child.id()).await {
+                Ok(session) => Some((sessions, session)),
+                Err(e) => {
+                    error!("Could not record the session: {}", e);
+                    None
+                }
+            },
+            None => None,
+        };
+        let process = GameProcess::spawn(java_runtime, child, command.working_directory, post_exit)
+            .with_pre_launch_output(pre_launch_output)
+            .with_instance_lock(lock, session);
+        info!("Game started, PID: {:?}", process.get_pid());
+        Ok(process)
+    }
+
+    /// Resolve everything `launch` needs without starting Java, from the files of the installed instance only
+    async fn prepare_launch(&self, path: &Path, profile: &UserProfile) -> Result<LaunchCommand, Box<dyn Error + Send + Sync>> {
+
+        self.launch_options.validate()?;
 
         let game_directory = self.get_game_dir();
-        println!("Game directory: {:?}", game_directory);
+        debug!("Game directory: {:?}", game_directory);
 
         let jre_path = path.join("jre");
-        println!("JRE path: {:?}", jre_path);
+        debug!("JRE path: {:?}", jre_path);
 
-        let java_version = self.get_java_from_manifest().await.unwrap();
-        println!("Java version: {:?}", java_version);
+        let version_data = self.get_installed_manifest().await?;
+        let java_version = version_data["javaVersion"]["majorVersion"]
+            .as_u64()
+            .ok_or("Java majorVersion not found in manifest")? as u32;
+        debug!("Java version: {:?}", java_version);
 
         //TODO: check if java version is compatible with the current java version
         //Check if java is already installed
@@ -37,90 +126,128 @@ impl<'a> Launch<'a> for Version<'a> {
         // Trouver java.exe (et pas javaw.exe pour avoir la console)
         let java_path = find_java_binary(&jre_path, &java_distribution, &java_version)
             .await
-            .expect("Java path not found");
-        println!("java path: {:?}", java_path);
-
-        let java_runtime = JavaRuntime::new(java_path);
+            .map_err(|e| format!("Java {} not found: {}", java_version, e))?;
+        debug!("Java path: {:?}", java_path);
 
-        //TODO: rewrite the arguments to use a generic method
+        if !self.get_client_path().exists() {
+            return Err(format!("Client jar not found at {:?}, is the instance installed?", self.get_client_path()).into());
+        }
 
-        let separator = match OS {
-            OperatingSystem::WINDOWS => ";",
-            _ => ":",
+        // The client jar comes last, after every library it may be patched by
+        let mut classpath = self.get_library_paths(&version_data)?;
+        classpath.push(self.get_client_path());
+        let joined_classpath = classpath
+            .iter()
+            .map(|path| path.to_string_lossy().to_string())
+            .collect::<Vec<_>>()
+            .join(OS.get_path_separator()?);
+
+        let mut context = self.get_argument_context(&version_data, profile, joined_classpath);
+        context.game_assets = self.get_game_assets_dir(&version_data).await?;
+        let quick_play_arguments = match &self.launch_options.quick_play {
+            Some(quick_play) => apply_quick_play(&version_data, quick_play, &mut context)?,
+            None => Vec::new(),
         };
 
-        let classpath = format!(
-            "{}{separator}{}",
-            self.get_all_libraries_dir().await.unwrap(),
-            self.get_client_path().to_string_lossy()
-        );
-
-
-        println!("classpath: {:?}", classpath);
-        println!("Game directory: {:?}", self.get_client_path());
-
+        let mut jvm_arguments = get_jvm_arguments(&version_data, &context)?;
+        jvm_arguments.extend(self.get_logging_arguments(&version_data).await?);
+        // Last, right before the main class, so the ones of the user win over the version ones
+        jvm_arguments.extend(self.launch_options.get_jvm_arguments()?);
+        if let Some(api_url) = &profile.yggdrasil_server {
+            // The agent must come before the main class to redirect authentication and skins
+            let injector = AuthlibInjector::new(&path.join("authlib-injector"));
+            jvm_arguments.splice(0..0, injector.get_jvm_arguments(api_url).await?);
+        }
 
-        let arguments = vec![
-            "-Xms1024M".to_string(),
-            "-Xmx2048M".to_string(),
-            "-Djava.library.path=".to_owned() + &self.get_natives_dir().display().to_string(),
-            //"-Dfabric.development=false".to_string(),
-            "-cp".to_string(),
+        let extra_game_arguments: Vec<String> = quick_play_arguments.into_iter().chain(self.launch_options.get_game_arguments()?).collect();
+        let mut game_arguments = get_game_arguments(&version_data, &context)?;
+        game_arguments.extend(extra_game_arguments.iter().cloned());
+        // Built a second time so exported commands do not leak the token
+        context.auth_access_token = ACCESS_TOKEN_PLACEHOLDER.to_string();
+        let mut redacted_game_arguments = get_game_arguments(&version_data, &context)?;
+        redacted_game_arguments.extend(extra_game_arguments);
+
+        Ok(LaunchCommand {
+            java: java_path,
+            wrappers: self.launch_options.get_wrappers()?,
+            jvm_arguments,
+            main_class: self.get_main_class(&version_data).await?,
+            game_arguments,
             classpath,
-            //TODO: make a generic method to get the main class from the manifest or from the version
-            //self.get_main_class_from_manifest().await.unwrap(),
-            "cpw.mods.modlauncher.Launcher".to_string(),
-            //"net.minecraft.client.Main".to_string(),
-            //"optifine.InstallerFrame".to_string(),
-            "--username".to_string(),
-            "Hamadi".to_string(),
-            "--version".to_string(),
-            self.minecraft_version.to_string(),
-            "--gameDir".to_string(),
-            game_directory.to_string_lossy().to_string(),
-            "--assetsDir".to_string(),
-            game_directory.join("assets").to_string_lossy().to_string(),
-            "--assetIndex".to_string(),
-            self.minecraft_version.to_string(),
-            "--uuid".to_string(),
-            "37fefc81-1e26-4d31-a988-74196affc99b".to_string(),
-            "--accessToken".to_string(),
-            "0:37fefc81-1e26-4d31-a988-74196affc99b".to_string(),
-            "--userProperties".to_string(),
-            "{}".to_string(),
-        ];
-
-        println!("Java arguments: {:#?}", arguments);
-
-
-        match java_runtime.execute(arguments, &game_directory).await {
-            Ok(mut child) => {
-                let (tx, rx) = oneshot::channel::<()>();
-
-                // Affiche les logs Java en temps réel dans le terminal
-                fn print_output(_: &(), buf: &[u8]) -> anyhow::Result<()> {
-                    print!("{}", String::from_utf8_lossy(buf));
-                    Ok(())
-                }
+            working_directory: game_directory,
+            environment: self.launch_options.environment.clone(),
+            removed_environment: self.launch_options.removed_environment.clone(),
+            redacted_game_arguments,
+        })
+    }
+}
 
-                if let Err(e) = java_runtime
-                    .handle_io(&mut child, print_output, print_output, rx, &())
-                    .await
-                {
-                    eprintln!("Erreur IO: {}", e);
-                }
+impl<'a> Version<'a> {
+    /// Save the files `prepare_launch` reads instead of downloading: the merged manifest and the log configuration
+    pub(crate) async fn install_launch_files(&self) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let version_data = self.save_installed_manifest().await?;
+        if let Some(config) = get_log_config(&version_data, &self.minecraft_version) {
+            self.install_log_config(&config).await?;
+        }
+        Ok(())
+    }
 
-                if let Some(pid) = child.id() {
-                    println!("Processus lancé avec succès, PID: {}", pid);
-                } else {
-                    println!("Processus lancé avec succès, PID non disponible");
-                }
+    /// `INST_*` variables the pre-launch and post-exit hooks see
+    fn get_hook_environment(&self, java_runtime: &JavaRuntime) -> BTreeMap<String, String> {
+        let game_directory = self.get_game_dir().to_string_lossy().to_string();
+        BTreeMap::from([
+            ("INST_NAME".to_string(), self.name.clone()),
+            ("INST_DIR".to_string(), game_directory.clone()),
+            ("INST_MC_DIR".to_string(), game_directory),
+            ("INST_JAVA".to_string(), java_runtime.0.to_string_lossy().to_string()),
+            ("INST_MC_VERSION".to_string(), self.minecraft_version.clone()),
+            ("INST_LOADER".to_string(), self.loader.clone()),
+            ("INST_LOADER_VERSION".to_string(), self.loader_version.clone()),
+        ])
+    }
 
-                // tx.send(()); // <- à utiliser si tu veux forcer l'arrêt du process plus tard
-            }
-            Err(e) => {
-                eprintln!("Erreur lors du lancement: {}", e);
-            }
+    /// Values of the `${...}` placeholders of the version JSON for this instance and account
+    fn get_argument_context(&self, version_data: &Value, profile: &UserProfile, classpath: String) -> ArgumentContext {
+        let options = &self.launch_options;
+        let resolution = options.get_resolution();
+        let mut features = BTreeMap::new();
+        // The account does not own the game, start it in demo mode instead of failing in-game
+        features.insert("is_demo_user".to_string(), profile.is_demo());
+        features.insert("has_custom_resolution".to_string(), resolution.is_some());
+
+        ArgumentContext {
+            auth_player_name: profile.name.clone(),
+            auth_uuid: profile.uuid.clone(),
+            auth_access_token: profile.access_token.clone(),
+            auth_xuid: String::new(),
+            clientid: String::new(),
+            user_type: match profile.user_type {
+                // Versions before 1.13 do not know Microsoft accounts
+                UserType::Msa if is_legacy_format(version_data) => UserType::Mojang.get_name().to_string(),
+                user_type => user_type.get_name().to_string(),
+            },
+            user_properties: profile.get_user_properties(),
+            // The client jar is named after the instance, loaders refer to it through the version name
+            version_name: self.name.clone(),
+            version_type: version_data["type"].as_str().unwrap_or("release").to_string(),
+            game_directory: self.get_game_dir(),
+            assets_root: self.get_assets_dir(),
+            assets_index_name: get_asset_index_name(version_data).unwrap_or_else(|| self.minecraft_version.clone()),
+            game_assets: self.get_assets_dir(),
+            natives_directory: self.get_natives_dir(),
+            library_directory: self.get_libraries_dir(),
+            classpath,
+            classpath_separator: OS.get_path_separator().unwrap_or(":").to_string(),
+            launcher_name: options.launcher_name.clone().unwrap_or_else(|| env!("CARGO_PKG_NAME").to_string()),
+            launcher_version: options.launcher_version.clone().unwrap_or_else(|| env!("CARGO_PKG_VERSION").to_string()),
+            resolution_width: resolution.map(|(width, _)| width),
+            resolution_height: resolution.map(|(_, height)| height),
+            // Filled by `apply_quick_play` when the version supports it
+            quick_play_path: None,
+            quick_play_singleplayer: None,
+            quick_play_multiplayer: None,
+            quick_play_realms: None,
+            features,
         }
     }
 }
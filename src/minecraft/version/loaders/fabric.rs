@@ -23,20 +23,25 @@ struct FabricProfile {
 }
 
 pub trait FabricLoader<'a> {
+    async fn get_fabric_profile(&self) -> Result<serde_json::Value, Box<dyn Error + Send + Sync>>;
     async fn get_fabric_manifest(&self) -> Result<String, Box<dyn Error + Send + Sync>>;
     async fn install_fabric(&self) -> Result<(), Box<dyn Error + Send + Sync>>;
     async fn download_fabric_libraries(&self) -> Result<(), Box<dyn Error + Send + Sync>>;
     async fn get_latest_fabric_loader_version(&self) -> Result<String, Box<dyn Error + Send + Sync>>;
 }
 impl<'a> FabricLoader<'a> for Version<'a> {
-    async fn get_fabric_manifest(&self) -> Result<String, Box<dyn Error + Send + Sync>> {
+    /// Launcher profile of the loader, it inherits from the vanilla version JSON
+    async fn get_fabric_profile(&self) -> Result<serde_json::Value, Box<dyn Error + Send + Sync>> {
         let url = format!(
             "https://meta.fabricmc.net/v2/versions/loader/{}/{}/profile/json",
             self.minecraft_version, self.loader_version
         );
         // Télécharger et parser le JSON
-        let response = HTTP_CLIENT.get(&url).send().await?;
-        let version_data: serde_json::Value = response.json().await?;
+        let response = HTTP_CLIENT.get(&url).send().await?.error_for_status()?;
+        Ok(response.json().await?)
+    }
+    async fn get_fabric_manifest(&self) -> Result<String, Box<dyn Error + Send + Sync>> {
+        let version_data = self.get_fabric_profile().await?;
 
         // Extraire la chaîne correctement
         let main_class = version_data["mainClass"]
@@ -38,6 +38,7 @@ struct NeoForgeProfile {
 }
 
 pub trait NeoForgeLoader<'a> {
+    async fn get_neoforge_profile(&self) -> Result<serde_json::Value, Box<dyn Error + Send + Sync>>;
     async fn get_neoforge_manifest(&self) -> Result<String, Box<dyn Error + Send + Sync>>;
     async fn install_neoforge(&self) -> Result<(), Box<dyn Error + Send + Sync>>;
     async fn download_neoforge_libraries(&self) -> Result<(), Box<dyn Error + Send + Sync>>;
@@ -47,7 +48,8 @@ pub trait NeoForgeLoader<'a> {
 }
 
 impl<'a> NeoForgeLoader<'a> for Version<'a> {
-    async fn get_neoforge_manifest(&self) -> Result<String, Box<dyn Error + Send + Sync>> {
+    /// Version JSON written by the installer, it inherits from the vanilla version JSON
+    async fn get_neoforge_profile(&self) -> Result<serde_json::Value, Box<dyn Error + Send + Sync>> {
         let version_id = self.get_version_id();
         let json_path = self.get_game_dir().join(format!("{}.json", version_id));
 
@@ -58,7 +60,11 @@ impl<'a> NeoForgeLoader<'a> for Version<'a> {
 
         // Lire et parser le fichier JSON
         let json_content = fs::read_to_string(json_path)?;
-        let version_data: serde_json::Value = serde_json::from_str(&json_content)?;
+        Ok(serde_json::from_str(&json_content)?)
+    }
+
+    async fn get_neoforge_manifest(&self) -> Result<String, Box<dyn Error + Send + Sync>> {
+        let version_data = self.get_neoforge_profile().await?;
 
         // Extraire la classe principale
         let main_class = version_data["mainClass"]
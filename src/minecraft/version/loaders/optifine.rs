@@ -1,6 +1,12 @@
 use std::error::Error;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use serde_json::json;
 use crate::minecraft::version::loaders::utils::assets::Assets;
-use crate::minecraft::version::loaders::utils::librairies::Libraries;
+use crate::minecraft::version::loaders::utils::client::Client;
+use crate::minecraft::version::loaders::utils::download::download_file;
+use crate::minecraft::version::loaders::utils::librairies::{resolve_library_paths, Libraries};
+use crate::minecraft::version::loaders::utils::manifest::LAUNCHWRAPPER_MAIN_CLASS;
 use crate::minecraft::version::loaders::utils::natives::Natives;
 use crate::minecraft::version::version::Version;
 use crate::utils::hosts::HTTP_CLIENT;
@@ -11,16 +17,30 @@ use crate::mkdir;
 
 use log::error;
 
+/// Tweak class LaunchWrapper loads OptiFine with
+const OPTIFINE_TWEAK_CLASS: &str = "optifine.OptiFineTweaker";
+/// LaunchWrapper of Mojang, OptiFine ships its own for versions running on Java 9+
+const LAUNCHWRAPPER_LIBRARY: &str = "net.minecraft:launchwrapper:1.12";
+const LAUNCHWRAPPER_PATH: &str = "net/minecraft/launchwrapper/1.12/launchwrapper-1.12.jar";
+const LAUNCHWRAPPER_URL: &str = "https://libraries.minecraft.net/net/minecraft/launchwrapper/1.12/launchwrapper-1.12.jar";
+const LAUNCHWRAPPER_SHA1: &str = "111e7bea9c968cdb3d06ef4632bf7ff0824d0f36";
+const LAUNCHWRAPPER_SIZE: u64 = 32999;
+
 pub trait OptifineLoader<'a> {
     async fn install_optifine(&self) -> Result<(), Box<dyn Error + Send + Sync>>;
     async fn download_optifine_client(&self) -> Result<(), Box<dyn Error + Send + Sync>>;
+    async fn get_optifine_profile(&self) -> Result<serde_json::Value, Box<dyn Error + Send + Sync>>;
+    async fn install_launchwrapper(&self) -> Result<(), Box<dyn Error + Send + Sync>>;
+    fn get_optifine_path(&self) -> PathBuf;
 }
 
 
 impl<'a> OptifineLoader<'a> for Version<'a> {
     async fn install_optifine(&self) -> Result<(), Box<dyn Error + Send + Sync>> {
 
+        self.download_client().await?;
         self.download_optifine_client().await?;
+        self.install_launchwrapper().await?;
         self.download_libraries().await?;
         self.download_natives().await?;
         self.download_assets().await?;
@@ -96,7 +116,10 @@ impl<'a> OptifineLoader<'a> for Version<'a> {
 
         // 6. Télécharger et sauvegarder
         let response = HTTP_CLIENT.get(&download_url).send().await?.bytes().await?;
-        let output_path = self.get_game_dir().join(format!("{}.jar",self.name));
+        let output_path = self.get_optifine_path();
+        if let Some(parent) = output_path.parent() {
+            mkdir!(parent);
+        }
 
         let mut file = async_fs::File::create(&output_path).await?;
         file.write_all(&response).await?;
@@ -110,4 +133,95 @@ impl<'a> OptifineLoader<'a> for Version<'a> {
         Ok(())
     }
 
-}
\ No newline at end of file
+    /// Launcher profile of OptiFine, the vanilla client started by LaunchWrapper with the OptiFine tweaker
+    async fn get_optifine_profile(&self) -> Result<serde_json::Value, Box<dyn Error + Send + Sync>> {
+        let launchwrapper = match get_launchwrapper_of_version(&self.get_optifine_path()).await? {
+            Some(version) => json!({ "name": format!("optifine:launchwrapper-of:{}", version) }),
+            None => get_launchwrapper_library(),
+        };
+        Ok(json!({
+            "id": format!("{}-OptiFine", self.minecraft_version),
+            "inheritsFrom": self.minecraft_version,
+            "mainClass": LAUNCHWRAPPER_MAIN_CLASS,
+            "libraries": [
+                { "name": format!("optifine:OptiFine:{}", self.minecraft_version) },
+                launchwrapper,
+            ],
+            "arguments": { "game": ["--tweakClass", OPTIFINE_TWEAK_CLASS] }
+        }))
+    }
+
+    /// Extract the LaunchWrapper bundled in the OptiFine jar, or download the one of Mojang
+    async fn install_launchwrapper(&self) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let optifine_path = self.get_optifine_path();
+        let launchwrapper_of = get_launchwrapper_of_version(&optifine_path).await?;
+        let library = match &launchwrapper_of {
+            Some(version) => json!({ "name": format!("optifine:launchwrapper-of:{}", version) }),
+            None => get_launchwrapper_library(),
+        };
+        let path = resolve_library_paths(&[library]).pop().ok_or("Invalid LaunchWrapper library")?;
+        let path = self.get_libraries_dir().join(path);
+        if path.exists() {
+            return Ok(());
+        }
+        if let Some(parent) = path.parent() {
+            mkdir!(parent);
+        }
+
+        match launchwrapper_of {
+            Some(version) => {
+                let entry = format!("launchwrapper-of-{}.jar", version);
+                let content = read_jar_entry(&optifine_path, &entry).await?.ok_or(format!("{} not found in the OptiFine jar", entry))?;
+                fs::write(&path, content).await?;
+            }
+            None => download_file(LAUNCHWRAPPER_URL, &path, LAUNCHWRAPPER_SHA1, LAUNCHWRAPPER_SIZE).await?,
+        }
+        println!("[LightyLauncher] LaunchWrapper installed to {}", path.display());
+        Ok(())
+    }
+
+    /// The OptiFine jar, a library of the profile
+    fn get_optifine_path(&self) -> PathBuf {
+        self.get_libraries_dir()
+            .join("optifine")
+            .join("OptiFine")
+            .join(&self.minecraft_version)
+            .join(format!("OptiFine-{}.jar", self.minecraft_version))
+    }
+}
+
+/// Library entry of the LaunchWrapper of Mojang, as the version JSONs of legacy Forge list it
+fn get_launchwrapper_library() -> serde_json::Value {
+    json!({
+        "name": LAUNCHWRAPPER_LIBRARY,
+        "downloads": { "artifact": {
+            "path": LAUNCHWRAPPER_PATH,
+            "url": LAUNCHWRAPPER_URL,
+            "sha1": LAUNCHWRAPPER_SHA1,
+            "size": LAUNCHWRAPPER_SIZE
+        } }
+    })
+}
+
+/// Version of the LaunchWrapper bundled in the OptiFine jar, listed in `launchwrapper-of.txt`
+async fn get_launchwrapper_of_version(optifine_path: &Path) -> Result<Option<String>, Box<dyn Error + Send + Sync>> {
+    let version = read_jar_entry(optifine_path, "launchwrapper-of.txt").await?;
+    Ok(version.map(|version| String::from_utf8_lossy(&version).trim().to_string()).filter(|version| !version.is_empty()))
+}
+
+async fn read_jar_entry(jar_path: &Path, entry: &str) -> Result<Option<Vec<u8>>, Box<dyn Error + Send + Sync>> {
+    let jar = fs::read(jar_path).await?;
+    let entry = entry.to_string();
+    tokio::task::spawn_blocking(move || -> Result<Option<Vec<u8>>, Box<dyn Error + Send + Sync>> {
+        let mut archive = zip::ZipArchive::new(std::io::Cursor::new(jar))?;
+        let mut file = match archive.by_name(&entry) {
+            Ok(file) => file,
+            Err(zip::result::ZipError::FileNotFound) => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        let mut content = Vec::new();
+        file.read_to_end(&mut content)?;
+        Ok(Some(content))
+    })
+    .await?
+}
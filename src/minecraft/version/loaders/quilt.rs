@@ -22,20 +22,25 @@ struct QuiltProfile {
 }
 
 pub trait QuiltLoader<'a> {
+    async fn get_quilt_profile(&self) -> Result<serde_json::Value, Box<dyn Error + Send + Sync>>;
     async fn get_quilt_manifest(&self) -> Result<String, Box<dyn Error + Send + Sync>>;
     async fn install_quilt(&self) -> Result<(), Box<dyn Error + Send + Sync>>;
     async fn download_quilt_libraries(&self) -> Result<(), Box<dyn Error + Send + Sync>>;
     async fn get_latest_quilt_loader_version(&self) -> Result<String, Box<dyn Error + Send + Sync>>;
 }
 impl<'a> QuiltLoader<'a> for Version<'a> {
-    async fn get_quilt_manifest(&self) -> Result<String, Box<dyn Error + Send + Sync>> {
+    /// Launcher profile of the loader, it inherits from the vanilla version JSON
+    async fn get_quilt_profile(&self) -> Result<serde_json::Value, Box<dyn Error + Send + Sync>> {
         let url = format!(
             "https://meta.quiltmc.org/v3/versions/loader/{}/{}/profile/json",
             self.minecraft_version, self.loader_version
         );
         // Télécharger et parser le JSON
-        let response = HTTP_CLIENT.get(&url).send().await?;
-        let version_data: serde_json::Value = response.json().await?;
+        let response = HTTP_CLIENT.get(&url).send().await?.error_for_status()?;
+        Ok(response.json().await?)
+    }
+    async fn get_quilt_manifest(&self) -> Result<String, Box<dyn Error + Send + Sync>> {
+        let version_data = self.get_quilt_profile().await?;
 
         // Extraire la chaîne correctement
         let main_class = version_data["mainClass"]
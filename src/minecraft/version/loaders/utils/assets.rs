@@ -1,19 +1,24 @@
 use serde_json::Value;
 use std::error::Error;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use tokio::fs;
 use crate::minecraft::version::loaders::utils::download::download_file;
+use crate::minecraft::version::loaders::utils::logging::get_log_config;
 use crate::minecraft::version::loaders::utils::manifest::Manifest;
 use crate::minecraft::version::version::Version;
 
 pub trait Assets<'a> {
     async fn download_assets(&self) -> Result<(), Box<dyn Error + Send + Sync>>;
+    async fn get_game_assets_dir(&self, version_data: &Value) -> Result<PathBuf, Box<dyn Error + Send + Sync>>;
 }
 impl<'a> Assets<'a> for Version<'a> {
     async fn download_assets(&self) -> Result<(), Box<dyn Error + Send + Sync>> {
 
         let version_data = self.get_manifest_version().await?;
 
+        if let Some(config) = get_log_config(&version_data, &self.minecraft_version) {
+            self.install_log_config(&config).await?;
+        }
 
         // Create directories
         let indexes_dir = self.get_assets_dir().join("indexes");
@@ -59,7 +64,8 @@ impl<'a> Assets<'a> for Version<'a> {
                 .ok_or("Asset index size not found")?;
 
             // ensuite continue le traitement...
-            let index_path = indexes_dir.join(format!("{}.json", self.minecraft_version));
+            // The game looks the index up by its ID (`--assetIndex`), not by the version
+            let index_path = indexes_dir.join(format!("{}.json", id));
             if !index_path.exists() {
                 println!("[LightyLauncher] Downloading asset index from: {}", url);
                 download_file(url, &index_path, sha1, size).await?;
@@ -109,6 +115,10 @@ impl<'a> Assets<'a> for Version<'a> {
 
             println!("[LightyLauncher] Downloaded {} new assets", downloaded);
 
+            if let Some(target) = get_legacy_assets_target(&index_json, id, &self.get_assets_dir(), &self.get_game_dir()) {
+                reconstruct_legacy_assets(&index_json, &objects_dir, &target).await?;
+            }
+
         }
 
         //TODO! recheck the logic of this part
@@ -118,9 +128,65 @@ impl<'a> Assets<'a> for Version<'a> {
         Ok(())
     }
 
+    /// Directory legacy versions read their assets from, `${game_assets}`
+    async fn get_game_assets_dir(&self, version_data: &Value) -> Result<PathBuf, Box<dyn Error + Send + Sync>> {
+        let Some(id) = get_asset_index_name(version_data) else {
+            return Ok(self.get_assets_dir());
+        };
+        let index_path = self.get_assets_dir().join("indexes").join(format!("{}.json", id));
+        if !index_path.exists() {
+            return Ok(self.get_assets_dir());
+        }
+
+        let index_json: Value = serde_json::from_str(&fs::read_to_string(&index_path).await?)?;
+        Ok(get_legacy_assets_target(&index_json, &id, &self.get_assets_dir(), &self.get_game_dir())
+            .unwrap_or_else(|| self.get_assets_dir()))
+    }
+
 }
 
 
+/// Where the assets of an old index must be copied with their real names.
+///
+/// Indexes up to 1.7.2 are `virtual` (`assets/virtual/<id>`), those before 1.6 `map_to_resources` (`<game dir>/resources`).
+fn get_legacy_assets_target(index_json: &Value, id: &str, assets_dir: &Path, game_dir: &Path) -> Option<PathBuf> {
+    if index_json["map_to_resources"].as_bool() == Some(true) {
+        Some(game_dir.join("resources"))
+    } else if index_json["virtual"].as_bool() == Some(true) {
+        Some(assets_dir.join("virtual").join(id))
+    } else {
+        None
+    }
+}
+
+/// Copy the hashed objects to `target` under their names, old versions cannot read the object store
+async fn reconstruct_legacy_assets(index_json: &Value, objects_dir: &Path, target: &Path) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let Some(objects) = index_json["objects"].as_object() else {
+        return Ok(());
+    };
+
+    for (asset_name, object) in objects {
+        let hash = object["hash"].as_str().ok_or(format!("Hash not found for asset {}", asset_name))?;
+        let asset_path = target.join(asset_name);
+        if asset_path.exists() {
+            continue;
+        }
+        if let Some(parent) = asset_path.parent() {
+            fs::create_dir_all(parent).await?;
+        }
+        fs::copy(objects_dir.join(&hash[0..2]).join(hash), &asset_path).await?;
+    }
+    Ok(())
+}
+
+/// Name of the asset index, passed to the game as `${assets_index_name}`
+pub(crate) fn get_asset_index_name(version_data: &Value) -> Option<String> {
+    version_data["assetIndex"]["id"]
+        .as_str()
+        .or_else(|| version_data["assets"].as_str())
+        .map(str::to_string)
+}
+
 async fn download_legacy_assets( asset_version: &str, assets_dir: &Path) -> Result<(), Box<dyn Error + Send + Sync>> {
     // For legacy asset structure, fetch from Mojang
     let url = format!("https://launchermeta.mojang.com/v1/packages/1863782e33ce7b584fc45b037325a1964e095d3e/{}.json", asset_version);
@@ -182,4 +248,32 @@ async fn download_legacy_assets( asset_version: &str, assets_dir: &Path) -> Resu
     }
 
     Ok(())
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn legacy_assets_are_copied_with_their_names() {
+        let dir = tempfile::tempdir().unwrap();
+        let assets_dir = dir.path().join("assets");
+        let objects_dir = assets_dir.join("objects");
+        std::fs::create_dir_all(objects_dir.join("ab")).unwrap();
+        std::fs::write(objects_dir.join("ab").join("abcdef"), b"sound").unwrap();
+
+        let index_json = json!({
+            "virtual": true,
+            "objects": { "sounds/step/grass1.ogg": { "hash": "abcdef", "size": 5 } }
+        });
+        let target = get_legacy_assets_target(&index_json, "legacy", &assets_dir, dir.path()).unwrap();
+        assert_eq!(target, assets_dir.join("virtual").join("legacy"));
+
+        reconstruct_legacy_assets(&index_json, &objects_dir, &target).await.unwrap();
+        assert_eq!(std::fs::read(target.join("sounds/step/grass1.ogg")).unwrap(), b"sound");
+
+        let pre_1_6 = json!({ "map_to_resources": true, "objects": {} });
+        assert_eq!(get_legacy_assets_target(&pre_1_6, "pre-1.6", &assets_dir, dir.path()).unwrap(), dir.path().join("resources"));
+        assert!(get_legacy_assets_target(&json!({ "objects": {} }), "1.7.10", &assets_dir, dir.path()).is_none());
+    }
+}
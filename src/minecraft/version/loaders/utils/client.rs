@@ -4,10 +4,12 @@ use serde_json::Value;
 use tokio::fs;
 use crate::minecraft::version::loaders::utils::download::download_file;
 use crate::minecraft::version::loaders::utils::manifest::Manifest;
+use crate::minecraft::version::mappings::ProguardMappings;
 use crate::minecraft::version::version::Version;
 
 pub trait Client<'a> {
     async fn download_client(&self) -> Result<(), Box<dyn Error + Send + Sync>>;
+    async fn download_client_mappings(&self) -> Result<Option<ProguardMappings>, Box<dyn Error + Send + Sync>>;
 }
 impl<'a> Client<'a> for Version<'a> {
     async fn download_client(&self) -> Result<(), Box<dyn Error + Send + Sync>> {
@@ -40,4 +42,22 @@ impl<'a> Client<'a> for Version<'a> {
         Ok(())
     }
 
+    /// Download the ProGuard mappings of the client next to its jar, `None` before 1.14.4
+    async fn download_client_mappings(&self) -> Result<Option<ProguardMappings>, Box<dyn Error + Send + Sync>> {
+        let mappings_path = self.get_game_dir().join(format!("{}-mappings.txt", self.name));
+
+        if !mappings_path.exists() {
+            let version_data = self.get_manifest_version().await?;
+            let Some(mappings) = version_data["downloads"]["client_mappings"].as_object() else {
+                return Ok(None);
+            };
+            let url = mappings["url"].as_str().ok_or("Client mappings URL not found")?;
+            let sha1 = mappings["sha1"].as_str().ok_or("Client mappings SHA1 not found")?;
+            let size = mappings["size"].as_u64().ok_or("Client mappings size not found")?;
+
+            println!("[LightyLauncher] Downloading client mappings from: {}", url);
+            download_file(url, &mappings_path, sha1, size).await?;
+        }
+        Ok(Some(ProguardMappings::load(&mappings_path).await?))
+    }
 }
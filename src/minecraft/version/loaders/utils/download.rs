@@ -1,11 +1,14 @@
 use std::error::Error;
 use std::path::Path;
 use sha1::{Sha1, Digest};
-use serde_json::Value;
 use tokio::fs;
-use crate::utils::system::{OS, OperatingSystem};
 
 pub(crate) async fn download_file(url: &str, path: &Path, expected_sha1: &str, expected_size: u64) -> Result<(), Box<dyn Error + Send + Sync>> {
+    download_verified_file(url, path, expected_sha1, Some(expected_size)).await
+}
+
+/// Same as [`download_file`], for files whose size is not published
+pub(crate) async fn download_verified_file(url: &str, path: &Path, expected_sha1: &str, expected_size: Option<u64>) -> Result<(), Box<dyn Error + Send + Sync>> {
     // Create parent directories if they don't exist
     if let Some(parent) = path.parent() {
         if !parent.exists() {
@@ -23,7 +26,9 @@ pub(crate) async fn download_file(url: &str, path: &Path, expected_sha1: &str, e
     let content = response.bytes().await?;
 
     // Verify size
-    if content.len() as u64 != expected_size {
+    if let Some(expected_size) = expected_size
+        && content.len() as u64 != expected_size
+    {
         return Err(format!(
             "Size mismatch for {}: expected {}, got {}",
             path.display(), expected_size, content.len()
@@ -48,47 +53,3 @@ pub(crate) async fn download_file(url: &str, path: &Path, expected_sha1: &str, e
 
     Ok(())
 }
-
-// Helper to determine if a library should be downloaded based on rules
-pub(crate) fn should_download_library(library: &Value) -> bool {
-    // If no rules exist, download the library
-    if !library.get("rules").is_some() {
-        return true;
-    }
-
-    let rules = match library["rules"].as_array() {
-        Some(r) => r,
-        None => return true, // No valid rules array means download by default
-    };
-
-    let mut allowed = false;
-
-    for rule in rules {
-        let action = rule["action"].as_str().unwrap_or("disallow");
-
-        // Default rule with no OS specification
-        if !rule.get("os").is_some() {
-            allowed = action == "allow";
-            continue;
-        }
-
-        // Rule with OS specification
-        if let Some(os) = rule["os"].as_object() {
-            let os_name = os["name"].as_str().unwrap_or("");
-
-            let matches_os = match OS {
-                OperatingSystem::WINDOWS => os_name == "windows",
-                OperatingSystem::OSX => os_name == "osx",
-                OperatingSystem::LINUX => os_name == "linux",
-                _ => false,
-            };
-
-            // Check if rule applies to current OS
-            if matches_os {
-                allowed = action == "allow";
-            }
-        }
-    }
-
-    allowed
-}
\ No newline at end of file
@@ -1,13 +1,15 @@
 use std::error::Error;
-use std::path::Path;
+use std::collections::HashSet;
+use std::path::PathBuf;
+use serde_json::Value;
+use crate::minecraft::version::arguments::{rules_allow, ArgumentContext};
 use crate::minecraft::version::loaders::utils::manifest::Manifest;
 use crate::minecraft::version::version::Version;
-use crate::utils::system::OS;
-use super::download::{download_file, should_download_library};
+use super::download::download_file;
 
 pub trait Libraries<'a> {
     async fn download_libraries(&self) -> Result<(), Box<dyn Error + Send + Sync>>;
-    async fn get_all_libraries_dir(&self) -> Result<String, Box<dyn Error + Send + Sync>>;
+    fn get_library_paths(&self, version_data: &Value) -> Result<Vec<PathBuf>, Box<dyn Error + Send + Sync>>;
 }
 impl<'a> Libraries<'a> for Version<'a> {
     async fn download_libraries(&self) -> Result<(), Box<dyn Error + Send + Sync>> {
@@ -22,7 +24,7 @@ impl<'a> Libraries<'a> for Version<'a> {
         let total = libraries.len();
 
         for (index, library) in libraries.iter().enumerate() {
-            if !should_download_library(library) {
+            if !is_library_allowed(library) {
                 continue;
             }
 
@@ -54,45 +56,162 @@ impl<'a> Libraries<'a> for Version<'a> {
         println!("[LightyLauncher] Downloaded {} new libraries", downloaded);
         Ok(())
     }
-    async fn get_all_libraries_dir(&self) -> Result<String, Box<dyn Error + Send + Sync>> {
-        // Obtenir le chemin de base des bibliothèques
-        let base_dir = self.get_libraries_dir();
-        let mut paths = Vec::new();
-
-        // Fonction récursive pour parcourir les dossiers
-        fn collect_jar_files(dir: &Path, paths: &mut Vec<String>) -> Result<(), Box<dyn Error + Send + Sync>> {
-            if dir.is_dir() {
-                for entry in std::fs::read_dir(dir)? {
-                    let entry = entry?;
-                    let path = entry.path();
-
-                    if path.is_dir() {
-                        // Récursion dans les sous-dossiers
-                        collect_jar_files(&path, paths)?;
-                    } else if path.is_file() {
-                        // Vérifier si c'est un fichier JAR
-                        if let Some(extension) = path.extension() {
-                            if extension == "jar" {
-                                paths.push(path.to_string_lossy().to_string());
-                            }
-                        }
-                    }
-                }
-            }
-            Ok(())
+
+    /// Jars of the libraries the version needs on this system, in classpath order
+    fn get_library_paths(&self, version_data: &Value) -> Result<Vec<PathBuf>, Box<dyn Error + Send + Sync>> {
+        let libraries = version_data["libraries"].as_array().ok_or("Libraries not found in version data")?;
+        let paths: Vec<PathBuf> = resolve_library_paths(libraries)
+            .into_iter()
+            .map(|path| self.get_libraries_dir().join(path))
+            .collect();
+
+        let missing: Vec<String> = paths.iter().filter(|path| !path.exists()).map(|path| path.to_string_lossy().to_string()).collect();
+        if !missing.is_empty() {
+            return Err(format!("Missing libraries, is the instance installed? {}", missing.join(", ")).into());
+        }
+        Ok(paths)
+    }
+}
+
+/// Whether the `rules` of a library allow it on this system: OS name, version and architecture.
+///
+/// Downloads, natives and the classpath all go through it so they agree on the libraries of the version.
+pub(crate) fn is_library_allowed(library: &Value) -> bool {
+    let rules = library["rules"].as_array().map(Vec::as_slice).unwrap_or_default();
+    rules_allow(rules, &ArgumentContext::default())
+}
+
+/// Relative paths of the libraries allowed on this system, as the official launcher builds the classpath.
+///
+/// Libraries keep the order of the version JSON and the first one of each `group:artifact[:classifier]` wins,
+/// so the libraries of a loader profile, merged before the vanilla ones, take precedence.
+pub(crate) fn resolve_library_paths(libraries: &[Value]) -> Vec<String> {
+    let mut seen = HashSet::new();
+    let mut paths = Vec::new();
+
+    for library in libraries {
+        if !is_library_allowed(library) {
+            continue;
         }
-        // Lancer la récursion à partir du dossier de base
-        collect_jar_files(&base_dir, &mut paths)?;
+        let Some(name) = library["name"].as_str() else {
+            continue;
+        };
+        let Some(coordinates) = MavenCoordinates::parse(name) else {
+            continue;
+        };
+        // Legacy natives only have classifiers, they are extracted instead of being on the classpath
+        let path = match library["downloads"]["artifact"]["path"].as_str() {
+            Some(path) => path.to_string(),
+            None if library.get("natives").is_some() => continue,
+            None => coordinates.get_path(),
+        };
+        if seen.insert(coordinates.get_key()) {
+            paths.push(path);
+        }
+    }
+    paths
+}
+
+/// `group:artifact:version[:classifier][@extension]`
+struct MavenCoordinates<'a> {
+    group: &'a str,
+    artifact: &'a str,
+    version: &'a str,
+    classifier: Option<&'a str>,
+    extension: &'a str,
+}
 
-        //TODO! check if is not infinite loop
-        if paths.is_empty() {
-            self.download_libraries().await?;
-            collect_jar_files(&base_dir, &mut paths)?;
+impl<'a> MavenCoordinates<'a> {
+    fn parse(name: &'a str) -> Option<Self> {
+        let (name, extension) = name.split_once('@').unwrap_or((name, "jar"));
+        let mut parts = name.split(':');
+        let coordinates = Self {
+            group: parts.next()?,
+            artifact: parts.next()?,
+            version: parts.next()?,
+            classifier: parts.next(),
+            extension,
+        };
+        parts.next().is_none().then_some(coordinates)
+    }
+
+    /// Identity of the library regardless of its version
+    fn get_key(&self) -> String {
+        match self.classifier {
+            Some(classifier) => format!("{}:{}:{}", self.group, self.artifact, classifier),
+            None => format!("{}:{}", self.group, self.artifact),
         }
+    }
 
-        // Détecter le séparateur de chemin selon l'OS
-        let sep = OS.get_path_separator()?; // retourne ";" ou ":" en fonction de l'OS
-        Ok(paths.join(sep))
+    /// Path in a Maven repository, e.g. `org/ow2/asm/asm/9.6/asm-9.6.jar`
+    fn get_path(&self) -> String {
+        let file_name = match self.classifier {
+            Some(classifier) => format!("{}-{}-{}.{}", self.artifact, self.version, classifier, self.extension),
+            None => format!("{}-{}.{}", self.artifact, self.version, self.extension),
+        };
+        format!("{}/{}/{}/{}", self.group.replace('.', "/"), self.artifact, self.version, file_name)
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use crate::minecraft::version::loaders::utils::manifest::merge_manifests;
+
+    #[test]
+    fn loader_libraries_win_over_vanilla_ones() {
+        let vanilla = json!({
+            "libraries": [
+                { "name": "org.ow2.asm:asm:9.3", "downloads": { "artifact": { "path": "org/ow2/asm/asm/9.3/asm-9.3.jar" } } },
+                { "name": "org.lwjgl:lwjgl:3.3.1", "downloads": { "artifact": { "path": "org/lwjgl/lwjgl/3.3.1/lwjgl-3.3.1.jar" } } },
+                { "name": "org.lwjgl:lwjgl:3.3.1:natives-linux", "downloads": { "artifact": { "path": "org/lwjgl/lwjgl/3.3.1/lwjgl-3.3.1-natives-linux.jar" } } },
+                { "name": "org.lwjgl.lwjgl:lwjgl-platform:2.9.4", "natives": { "linux": "natives-linux" }, "downloads": { "classifiers": {} } },
+                { "name": "com.mojang:text2speech:1.10.3", "rules": [{ "action": "allow" }, { "action": "disallow", "os": { "name": "linux" } }, { "action": "disallow", "os": { "name": "windows" } }, { "action": "disallow", "os": { "name": "osx" } }] }
+            ]
+        });
+        let fabric = json!({
+            "libraries": [
+                { "name": "org.ow2.asm:asm:9.6", "url": "https://maven.fabricmc.net/" },
+                { "name": "net.fabricmc:fabric-loader:0.15.10", "url": "https://maven.fabricmc.net/" }
+            ]
+        });
+
+        let merged = merge_manifests(vanilla, fabric);
+        let paths = resolve_library_paths(merged["libraries"].as_array().unwrap());
+        assert_eq!(paths, vec![
+            "org/ow2/asm/asm/9.6/asm-9.6.jar",
+            "net/fabricmc/fabric-loader/0.15.10/fabric-loader-0.15.10.jar",
+            "org/lwjgl/lwjgl/3.3.1/lwjgl-3.3.1.jar",
+            "org/lwjgl/lwjgl/3.3.1/lwjgl-3.3.1-natives-linux.jar",
+        ]);
+    }
+
+    #[test]
+    fn os_version_and_arch_rules_are_checked() {
+        let os = crate::utils::system::OS.get_simple_name().unwrap();
+        let rule = |version: &str| json!({ "name": "org.lwjgl.lwjgl:lwjgl:2.9.0", "rules": [
+            { "action": "allow" },
+            { "action": "disallow", "os": { "name": os, "version": version } }
+        ] });
+        // As lwjgl 2.9.0 on Mac OS X 10.5
+        let other_version = rule("^0\\.0\\.\\d$");
+        assert!(is_library_allowed(&other_version));
+        assert_eq!(resolve_library_paths(&[other_version]), vec!["org/lwjgl/lwjgl/lwjgl/2.9.0/lwjgl-2.9.0.jar"]);
+
+        let this_version = rule(&format!("^{}$", regex::escape(&os_info::get().version().to_string())));
+        assert!(!is_library_allowed(&this_version));
+        assert!(resolve_library_paths(&[this_version]).is_empty());
+
+        let x86_only = json!({ "name": "com.example:x86:1.0", "rules": [{ "action": "allow", "os": { "arch": "x86" } }] });
+        assert_eq!(is_library_allowed(&x86_only), cfg!(target_arch = "x86"));
+    }
+
+    #[test]
+    fn maven_names_are_resolved_to_paths() {
+        let coordinates = MavenCoordinates::parse("net.neoforged:neoforge:20.4.80:universal@zip").unwrap();
+        assert_eq!(coordinates.get_key(), "net.neoforged:neoforge:universal");
+        assert_eq!(coordinates.get_path(), "net/neoforged/neoforge/20.4.80/neoforge-20.4.80-universal.zip");
+        assert!(MavenCoordinates::parse("not-a-library").is_none());
+    }
+}
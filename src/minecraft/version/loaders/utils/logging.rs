@@ -0,0 +1,149 @@
+use std::error::Error;
+use std::path::PathBuf;
+use serde_json::Value;
+use version_compare::{compare_to, Cmp};
+use crate::minecraft::version::loaders::utils::download::download_verified_file;
+use crate::minecraft::version::version::Version;
+
+/// Flag disabling message lookups, the Log4Shell fix for versions shipping log4j 2.10+
+const NO_LOOKUPS_ARGUMENT: &str = "-Dlog4j2.formatMsgNoLookups=true";
+
+/// Log4j configuration the game must be started with
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct LogConfig {
+    pub(crate) id: String,
+    pub(crate) url: String,
+    pub(crate) sha1: String,
+    pub(crate) size: Option<u64>,
+    /// JVM argument with a `${path}` placeholder, e.g. `-Dlog4j.configurationFile=${path}`
+    pub(crate) argument: String,
+}
+
+impl LogConfig {
+    fn patched(id: &str, sha1: &str) -> Self {
+        Self {
+            id: id.to_string(),
+            url: format!("https://launcher.mojang.com/v1/objects/{}/{}", sha1, id),
+            sha1: sha1.to_string(),
+            size: None,
+            argument: "-Dlog4j.configurationFile=${path}".to_string(),
+        }
+    }
+}
+
+pub trait Logging<'a> {
+    async fn get_logging_arguments(&self, version_data: &Value) -> Result<Vec<String>, Box<dyn Error + Send + Sync>>;
+}
+
+impl<'a> Logging<'a> for Version<'a> {
+    /// JVM arguments configuring log4j, with the Log4Shell mitigations of the version.
+    ///
+    /// The configuration must have been downloaded by [`Version::install_log_config`].
+    async fn get_logging_arguments(&self, version_data: &Value) -> Result<Vec<String>, Box<dyn Error + Send + Sync>> {
+        let mut arguments = Vec::new();
+        if let Some(config) = get_log_config(version_data, &self.minecraft_version) {
+            let path = self.get_log_config_path(&config);
+            if !path.exists() {
+                return Err(format!("Log configuration not found at {:?}, is the instance installed?", path).into());
+            }
+            arguments.push(config.argument.replace("${path}", &path.to_string_lossy()));
+        }
+        if needs_no_lookups(&self.minecraft_version) {
+            arguments.push(NO_LOOKUPS_ARGUMENT.to_string());
+        }
+        Ok(arguments)
+    }
+}
+
+impl<'a> Version<'a> {
+    /// Download `config` into `assets/log_configs` if missing
+    pub(crate) async fn install_log_config(&self, config: &LogConfig) -> Result<PathBuf, Box<dyn Error + Send + Sync>> {
+        let path = self.get_log_config_path(config);
+        if !path.exists() {
+            println!("[LightyLauncher] Downloading log configuration from: {}", config.url);
+            download_verified_file(&config.url, &path, &config.sha1, config.size).await?;
+        }
+        Ok(path)
+    }
+
+    fn get_log_config_path(&self, config: &LogConfig) -> PathBuf {
+        self.get_assets_dir().join("log_configs").join(&config.id)
+    }
+}
+
+/// Log4j configuration of `version_data`, replaced by Mojang's patched one for the versions affected by Log4Shell
+pub(crate) fn get_log_config(version_data: &Value, minecraft_version: &str) -> Option<LogConfig> {
+    let logging = &version_data["logging"]["client"];
+    let file = &logging["file"];
+    let id = file["id"].as_str()?;
+
+    let is_between = |min: &str, max: &str| {
+        compare_to(minecraft_version, min, Cmp::Ge).unwrap_or(false) && compare_to(minecraft_version, max, Cmp::Lt).unwrap_or(false)
+    };
+    if is_between("1.7", "1.12") {
+        return Some(LogConfig::patched("log4j2_17-111.xml", "dd2b723346a8dcd48e7f4d245f6bf09e98db9696"));
+    }
+    if is_between("1.12", "1.17") {
+        return Some(LogConfig::patched("log4j2_112-116.xml", "02937d122c86ce73319ef9975b58896fc1b491d1"));
+    }
+
+    Some(LogConfig {
+        id: id.to_string(),
+        url: file["url"].as_str()?.to_string(),
+        sha1: file["sha1"].as_str()?.to_string(),
+        size: file["size"].as_u64(),
+        argument: logging["argument"].as_str()?.to_string(),
+    })
+}
+
+/// 1.17 and 1.18 ship a log4j recent enough to only need lookups disabled, 1.18.1 is fixed
+fn needs_no_lookups(minecraft_version: &str) -> bool {
+    compare_to(minecraft_version, "1.17", Cmp::Ge).unwrap_or(false) && compare_to(minecraft_version, "1.18.1", Cmp::Lt).unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn version_data(id: &str) -> Value {
+        json!({
+            "logging": {
+                "client": {
+                    "argument": "-Dlog4j.configurationFile=${path}",
+                    "file": {
+                        "id": id,
+                        "sha1": "bd65e7d2e3c237be76cfbef4c2405033d7f91521",
+                        "size": 888,
+                        "url": format!("https://piston-data.mojang.com/v1/objects/bd65e7d2e3c237be76cfbef4c2405033d7f91521/{}", id)
+                    },
+                    "type": "log4j2-xml"
+                }
+            }
+        })
+    }
+
+    #[test]
+    fn patched_configs_replace_the_vulnerable_ones() {
+        assert_eq!(get_log_config(&version_data("client-1.7.xml"), "1.7.10").unwrap().id, "log4j2_17-111.xml");
+        assert_eq!(get_log_config(&version_data("client-1.12.xml"), "1.12.2").unwrap().id, "log4j2_112-116.xml");
+        assert_eq!(get_log_config(&version_data("client-1.12.xml"), "1.16.5").unwrap().id, "log4j2_112-116.xml");
+
+        let config = get_log_config(&version_data("client-1.12.xml"), "1.20.1").unwrap();
+        assert_eq!(config.id, "client-1.12.xml");
+        assert_eq!(config.size, Some(888));
+        assert_eq!(config.argument, "-Dlog4j.configurationFile=${path}");
+
+        // Before 1.7 the game did not use log4j
+        assert!(get_log_config(&json!({}), "1.6.4").is_none());
+    }
+
+    #[test]
+    fn lookups_are_disabled_on_1_17_and_1_18() {
+        assert!(!needs_no_lookups("1.16.5"));
+        assert!(needs_no_lookups("1.17.1"));
+        assert!(needs_no_lookups("1.18"));
+        assert!(!needs_no_lookups("1.18.1"));
+        assert!(!needs_no_lookups("1.20.2"));
+    }
+}
@@ -1,7 +1,10 @@
 use std::error::Error;
+use log::warn;
 use serde_json::Value;
+use tokio::fs;
 use crate::minecraft::version::loaders::fabric::FabricLoader;
 use crate::minecraft::version::loaders::neoforge::NeoForgeLoader;
+use crate::minecraft::version::loaders::optifine::OptifineLoader;
 use crate::minecraft::version::loaders::quilt::QuiltLoader;
 use crate::minecraft::version::version::Version;
 
@@ -10,6 +13,8 @@ pub trait Manifest<'a> {
     async fn get_manifest_version(&self) -> Result<Value, Box<dyn Error + Send + Sync>>;
     async fn get_java_from_manifest(&self) -> Result<u32, Box<dyn Error + Send + Sync>>;
     async fn get_main_class_from_manifest(&self) -> Result<String, Box<dyn Error + Send + Sync>>;
+    async fn get_loader_profile(&self) -> Result<Option<Value>, Box<dyn Error + Send + Sync>>;
+    async fn get_merged_manifest(&self) -> Result<Value, Box<dyn Error + Send + Sync>>;
 
 }
 impl<'a> Manifest<'a> for Version<'a> {
@@ -50,46 +55,203 @@ impl<'a> Manifest<'a> for Version<'a> {
         Ok(java_version as u32)
     }
 
+    /// Main class of the merged profile, cached for the lifetime of the version
     async fn get_main_class_from_manifest(&self) -> Result<String, Box<dyn Error + Send + Sync>> {
+        self.main_class
+            .get_or_try_init(|| async { resolve_main_class(&self.get_merged_manifest().await?) })
+            .await
+            .cloned()
+    }
 
+    /// Profile of the loader, `None` for vanilla
+    async fn get_loader_profile(&self) -> Result<Option<Value>, Box<dyn Error + Send + Sync>> {
         match self.loader.as_str() {
-            "fabric" => {
-                println!("[LightyLauncher] Fabric Main class {:#?}", self.get_fabric_manifest().await?);
-                Ok(self.get_fabric_manifest().await?)
-            }
-            "vanilla" => {
-                let version_data = self.get_manifest_version().await?;
-                let main_class = version_data["mainClass"]
-                    .as_str()
-                    .ok_or("Main class not found in manifest")?;
-                Ok(main_class.to_string())
-            }
-            "quilt" => {
-                println!("[LightyLauncher] Quilt Main class {:#?}", self.get_quilt_manifest().await?);
-                Ok(self.get_quilt_manifest().await?)
+            "vanilla" => Ok(None),
+            "fabric" => Ok(Some(self.get_fabric_profile().await?)),
+            "quilt" => Ok(Some(self.get_quilt_profile().await?)),
+            "neoforge" => Ok(Some(self.get_neoforge_profile().await?)),
+            "optifine" => Ok(Some(self.get_optifine_profile().await?)),
+            loader => Err(format!("Loader {} not supported", loader).into()),
+        }
+    }
+
+    /// Vanilla version JSON with the loader profile applied on top of it, as the official launcher resolves `inheritsFrom`
+    async fn get_merged_manifest(&self) -> Result<Value, Box<dyn Error + Send + Sync>> {
+        let version_data = self.get_manifest_version().await?;
+        Ok(match self.get_loader_profile().await? {
+            Some(profile) => merge_manifests(version_data, profile),
+            None => version_data,
+        })
+    }
+}
+
+impl<'a> Version<'a> {
+    /// Fetch the merged manifest and keep it in the game directory, for launching without network
+    pub(crate) async fn save_installed_manifest(&self) -> Result<Value, Box<dyn Error + Send + Sync>> {
+        let version_data = self.get_merged_manifest().await?;
+        let path = self.get_game_dir().join(INSTALLED_MANIFEST_FILE);
+        let tmp_path = path.with_extension("json.tmp");
+        fs::write(&tmp_path, serde_json::to_vec_pretty(&version_data)?).await?;
+        fs::rename(&tmp_path, &path).await?;
+        Ok(version_data)
+    }
+
+    /// Merged manifest saved by [`Self::save_installed_manifest`]
+    pub(crate) async fn get_installed_manifest(&self) -> Result<Value, Box<dyn Error + Send + Sync>> {
+        let path = self.get_game_dir().join(INSTALLED_MANIFEST_FILE);
+        if !path.exists() {
+            return Err(format!("Version JSON not found at {:?}, is the instance installed?", path).into());
+        }
+        Ok(serde_json::from_str(&fs::read_to_string(&path).await?)?)
+    }
+
+    /// Main class of `version_data`, the merged manifest of this version, cached like [`Manifest::get_main_class_from_manifest`]
+    pub(crate) async fn get_main_class(&self, version_data: &Value) -> Result<String, Box<dyn Error + Send + Sync>> {
+        self.main_class
+            .get_or_try_init(|| async { resolve_main_class(version_data) })
+            .await
+            .cloned()
+    }
+}
+
+/// Merged manifest of an installed instance, in its game directory
+pub(crate) const INSTALLED_MANIFEST_FILE: &str = "version.json";
+
+/// Main class LaunchWrapper profiles (legacy Forge, OptiFine, versions before 1.6) start through
+pub(crate) const LAUNCHWRAPPER_MAIN_CLASS: &str = "net.minecraft.launchwrapper.Launch";
+
+/// Main class of a merged manifest.
+///
+/// LaunchWrapper without `--tweakClass` starts the game with its `VanillaTweaker`, a loader profile missing its tweaker is only worth a warning.
+pub(crate) fn resolve_main_class(version_data: &Value) -> Result<String, Box<dyn Error + Send + Sync>> {
+    let main_class = version_data["mainClass"].as_str().ok_or("Main class not found in manifest")?;
+    if main_class == LAUNCHWRAPPER_MAIN_CLASS && get_tweak_classes(version_data).is_empty() {
+        warn!("{} is the main class but no tweak class is given, the game starts without loader", main_class);
+    }
+    Ok(main_class.to_string())
+}
+
+/// `--tweakClass` values of the game arguments, in both argument formats
+pub(crate) fn get_tweak_classes(version_data: &Value) -> Vec<String> {
+    let arguments: Vec<&str> = match version_data["minecraftArguments"].as_str() {
+        Some(arguments) => arguments.split_whitespace().collect(),
+        None => version_data["arguments"]["game"]
+            .as_array()
+            .map(|arguments| arguments.iter().filter_map(Value::as_str).collect())
+            .unwrap_or_default(),
+    };
+    arguments
+        .windows(2)
+        .filter(|pair| pair[0] == "--tweakClass")
+        .map(|pair| pair[1].to_string())
+        .collect()
+}
+
+/// Apply a child version JSON (`inheritsFrom`) on its parent.
+///
+/// Child libraries come first, arguments are appended to the parent ones, any other field is overridden.
+/// Plain game arguments of a child are appended to `minecraftArguments` when the parent predates 1.13.
+pub(crate) fn merge_manifests(mut parent: Value, child: Value) -> Value {
+    let Value::Object(child) = child else {
+        return parent;
+    };
+
+    for (key, value) in child {
+        match key.as_str() {
+            "inheritsFrom" => {}
+            "libraries" => {
+                let mut libraries = value.as_array().cloned().unwrap_or_default();
+                libraries.extend(parent["libraries"].as_array().cloned().unwrap_or_default());
+                parent["libraries"] = Value::Array(libraries);
             }
-            "optifine" => {
-                //TODO: OptiFine doesn't have a standard manifest, so we need to handle it differently
-                // let version_data = self.get_manifest_version().await?;
-                // let main_class = version_data["mainClass"]
-                //     .as_str()
-                //     .ok_or("Main class not found in manifest")?;
-                // Ok(main_class.to_string())
-                Ok("optifine.InstallerFrame".to_string())
+            "arguments" if parent["arguments"].is_null() && parent["minecraftArguments"].is_string() => {
+                let mut arguments = parent["minecraftArguments"].as_str().unwrap_or_default().to_string();
+                for argument in value["game"].as_array().into_iter().flatten().filter_map(Value::as_str) {
+                    arguments.push(' ');
+                    arguments.push_str(argument);
+                }
+                parent["minecraftArguments"] = Value::String(arguments);
             }
-            "neoforge" => {
-                Ok(self.get_neoforge_manifest().await?)
+            "arguments" => {
+                for kind in ["game", "jvm"] {
+                    let Some(extra) = value[kind].as_array() else {
+                        continue;
+                    };
+                    let mut arguments = parent["arguments"][kind].as_array().cloned().unwrap_or_default();
+                    arguments.extend(extra.iter().cloned());
+                    parent["arguments"][kind] = Value::Array(arguments);
+                }
             }
+            _ => parent[key] = value,
+        }
+    }
+    parent
+}
 
-            _ => {
-                return Err("Loader not supported".into());
-            }
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
 
-        }
+    #[test]
+    fn loader_profile_is_applied_on_vanilla() {
+        let vanilla = json!({
+            "id": "1.20.2",
+            "mainClass": "net.minecraft.client.main.Main",
+            "assetIndex": { "id": "8" },
+            "libraries": [{ "name": "org.ow2.asm:asm:9.3" }],
+            "arguments": { "game": ["--username", "${auth_player_name}"], "jvm": ["-cp", "${classpath}"] }
+        });
+        let fabric = json!({
+            "id": "fabric-loader-0.15.10-1.20.2",
+            "inheritsFrom": "1.20.2",
+            "mainClass": "net.fabricmc.loader.impl.launch.knot.KnotClient",
+            "libraries": [{ "name": "org.ow2.asm:asm:9.6" }],
+            "arguments": { "game": [], "jvm": ["-DFabricMcEmu= net.minecraft.client.main.Main "] }
+        });
 
+        let merged = merge_manifests(vanilla, fabric);
+        assert_eq!(merged["id"], "fabric-loader-0.15.10-1.20.2");
+        assert_eq!(merged["mainClass"], "net.fabricmc.loader.impl.launch.knot.KnotClient");
+        assert_eq!(merged["assetIndex"]["id"], "8");
+        assert_eq!(merged["libraries"][0]["name"], "org.ow2.asm:asm:9.6");
+        assert_eq!(merged["libraries"][1]["name"], "org.ow2.asm:asm:9.3");
+        assert_eq!(merged["arguments"]["game"].as_array().unwrap().len(), 2);
+        assert_eq!(merged["arguments"]["jvm"][2], "-DFabricMcEmu= net.minecraft.client.main.Main ");
+        assert!(merged.get("inheritsFrom").is_none());
     }
 
+    #[test]
+    fn launchwrapper_profiles_keep_their_tweak_classes() {
+        let vanilla = json!({
+            "id": "1.12.2",
+            "mainClass": "net.minecraft.client.main.Main",
+            "minecraftArguments": "--username ${auth_player_name} --version ${version_name}"
+        });
+        let optifine = json!({
+            "inheritsFrom": "1.12.2",
+            "mainClass": "net.minecraft.launchwrapper.Launch",
+            "arguments": { "game": ["--tweakClass", "optifine.OptiFineTweaker"] }
+        });
+        let merged = merge_manifests(vanilla.clone(), optifine);
+        assert_eq!(merged["minecraftArguments"], "--username ${auth_player_name} --version ${version_name} --tweakClass optifine.OptiFineTweaker");
+        assert!(merged.get("arguments").is_none());
+        assert_eq!(get_tweak_classes(&merged), vec!["optifine.OptiFineTweaker"]);
+        assert_eq!(resolve_main_class(&merged).unwrap(), LAUNCHWRAPPER_MAIN_CLASS);
 
+        // Legacy Forge replaces the arguments entirely
+        let forge = json!({
+            "inheritsFrom": "1.7.10",
+            "mainClass": "net.minecraft.launchwrapper.Launch",
+            "minecraftArguments": "--username ${auth_player_name} --tweakClass cpw.mods.fml.common.launcher.FMLTweaker"
+        });
+        let merged = merge_manifests(vanilla.clone(), forge);
+        assert_eq!(get_tweak_classes(&merged), vec!["cpw.mods.fml.common.launcher.FMLTweaker"]);
 
-
+        assert_eq!(resolve_main_class(&vanilla).unwrap(), "net.minecraft.client.main.Main");
+        // Versions before 1.6 run through LaunchWrapper and its default tweaker
+        let legacy = json!({ "mainClass": "net.minecraft.launchwrapper.Launch", "minecraftArguments": "--username x" });
+        assert_eq!(resolve_main_class(&legacy).unwrap(), LAUNCHWRAPPER_MAIN_CLASS);
+        assert!(resolve_main_class(&json!({})).is_err());
+    }
 }
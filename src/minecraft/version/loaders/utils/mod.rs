@@ -3,4 +3,5 @@ pub(crate) mod client;
 pub(crate) mod librairies;
 pub(crate) mod natives;
 pub(crate) mod manifest;
-pub(crate) mod download;
\ No newline at end of file
+pub(crate) mod download;
+pub(crate) mod logging;
\ No newline at end of file
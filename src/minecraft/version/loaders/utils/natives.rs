@@ -6,7 +6,8 @@ use tokio::fs;
 use crate::minecraft::version::loaders::utils::manifest::Manifest;
 use crate::minecraft::version::version::Version;
 use crate::utils::system::{OS, ARCHITECTURE, OperatingSystem, Architecture};
-use super::download::{download_file, should_download_library};
+use super::download::download_file;
+use super::librairies::is_library_allowed;
 pub trait Natives<'a> {
     async fn download_natives(&self) -> Result<(), Box<dyn Error + Send + Sync>>;
 }
@@ -30,7 +31,7 @@ impl<'a> Natives<'a> for Version<'a> {
         // Count natives first
         let mut native_count = 0;
         for library in libraries.iter() {
-            if !should_download_library(library) {
+            if !is_library_allowed(library) {
                 continue;
             }
 
@@ -54,7 +55,7 @@ impl<'a> Natives<'a> for Version<'a> {
         // Now download each native
         let mut current = 0;
         for library in libraries.iter() {
-            if !should_download_library(library) {
+            if !is_library_allowed(library) {
                 continue;
             }
 
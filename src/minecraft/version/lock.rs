@@ -0,0 +1,287 @@
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+use directories::ProjectDirs;
+use serde::{Deserialize, Serialize};
+use sysinfo::{Pid, PidExt, ProcessExt, System, SystemExt};
+use tokio::fs;
+use tokio::io::AsyncWriteExt;
+
+/// Lock file written in the game directory while the instance is in use
+pub const LOCK_FILE: &str = "instance.lock";
+
+/// What holds the lock of an instance
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LockOperation {
+    /// The game is running, the PID is the one of Java
+    Running,
+    Installing,
+    Uninstalling,
+}
+
+impl Display for LockOperation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LockOperation::Running => write!(f, "running"),
+            LockOperation::Installing => write!(f, "being installed"),
+            LockOperation::Uninstalling => write!(f, "being uninstalled"),
+        }
+    }
+}
+
+/// Content of [`LOCK_FILE`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InstanceLock {
+    pub operation: LockOperation,
+    pub pid: u32,
+    /// Seconds since the Unix epoch
+    pub started_at: u64,
+    /// Name of the account the game was launched with
+    pub account: Option<String>,
+}
+
+impl InstanceLock {
+    /// Whether the process holding the lock is gone, a PID reused by a newer process counts as gone
+    pub fn is_stale(&self) -> bool {
+        !is_process_alive(self.pid, self.started_at)
+    }
+}
+
+/// Whether `pid` is a process started by `started_at` (seconds since the Unix epoch), not a newer one reusing the PID
+pub(crate) fn is_process_alive(pid: u32, started_at: u64) -> bool {
+    let pid = Pid::from_u32(pid);
+    let mut system = System::new();
+    if !system.refresh_process(pid) {
+        return false;
+    }
+    // Start times are rounded to the second
+    system.process(pid).is_some_and(|process| process.start_time() <= started_at + 1)
+}
+
+/// Errors raised while taking the lock of an instance
+#[derive(Debug)]
+pub enum LockError {
+    /// Another launch or operation holds the lock
+    Locked(InstanceLock),
+    Io(std::io::Error),
+}
+
+impl Display for LockError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LockError::Locked(lock) => write!(f, "The instance is already {} (PID {})", lock.operation, lock.pid),
+            LockError::Io(e) => write!(f, "Could not write the instance lock: {}", e),
+        }
+    }
+}
+
+impl Error for LockError {}
+
+impl From<std::io::Error> for LockError {
+    fn from(error: std::io::Error) -> Self {
+        LockError::Io(error)
+    }
+}
+
+/// Lock held by this launcher, the file is removed when it is dropped unless it was handed to the game
+#[derive(Debug)]
+pub(crate) struct InstanceLockGuard {
+    path: PathBuf,
+    lock: InstanceLock,
+    /// Cleared by [`Self::set_pid`], the game may outlive the launcher and its lock must stay
+    armed: bool,
+}
+
+impl InstanceLockGuard {
+    /// Take the lock of the instance in `game_dir` for this process, replacing a stale one
+    pub(crate) async fn acquire(game_dir: &Path, operation: LockOperation, account: Option<String>) -> Result<Self, LockError> {
+        fs::create_dir_all(game_dir).await?;
+        let path = game_dir.join(LOCK_FILE);
+        let lock = InstanceLock { operation, pid: std::process::id(), started_at: unix_now(), account };
+        let content = serde_json::to_vec_pretty(&lock).map_err(std::io::Error::other)?;
+        // Written aside then linked in place, another launcher never reads a partial lock
+        let tmp_path = write_tmp_file(&path, &content).await?;
+
+        let result = loop {
+            match fs::hard_link(&tmp_path, &path).await {
+                Ok(()) => break Ok(Self { path, lock, armed: true }),
+                Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {
+                    // Complete locks only, an unreadable one is corrupted
+                    match read_lock_file(&path).await {
+                        Some(current) if !current.is_stale() => break Err(LockError::Locked(current)),
+                        _ => remove_if_exists(&path).await?,
+                    }
+                }
+                Err(e) => break Err(e.into()),
+            }
+        };
+        remove_if_exists(&tmp_path).await?;
+        result
+    }
+
+    /// Hand the lock over to the game process once it is started.
+    ///
+    /// The start time moves with it, the process started after the lock was taken and would look like a reused PID.
+    /// From then on dropping the guard keeps the lock, it turns stale when the game exits.
+    pub(crate) async fn set_pid(&mut self, pid: u32) -> Result<(), LockError> {
+        self.lock.pid = pid;
+        self.lock.started_at = unix_now();
+        let content = serde_json::to_vec_pretty(&self.lock).map_err(std::io::Error::other)?;
+        let tmp_path = write_tmp_file(&self.path, &content).await?;
+        fs::rename(&tmp_path, &self.path).await?;
+        self.armed = false;
+        Ok(())
+    }
+
+    /// Remove the lock now, to call once the game has exited
+    pub(crate) fn release(mut self) {
+        self.armed = true;
+    }
+}
+
+impl Drop for InstanceLockGuard {
+    fn drop(&mut self) {
+        if !self.armed {
+            return;
+        }
+        // Only remove the file if it is still ours, uninstalling removes it with the game directory
+        let ours = std::fs::read(&self.path)
+            .ok()
+            .and_then(|content| serde_json::from_slice::<InstanceLock>(&content).ok())
+            .is_some_and(|lock| lock == self.lock);
+        if ours {
+            let _ = std::fs::remove_file(&self.path);
+        }
+    }
+}
+
+/// Lock of the instance in `game_dir`, `None` if it is free or the lock is stale
+pub async fn read_lock(game_dir: &Path) -> Option<InstanceLock> {
+    read_lock_file(&game_dir.join(LOCK_FILE)).await.filter(|lock| !lock.is_stale())
+}
+
+/// Name and lock of the instances of the launcher whose game is running
+pub async fn get_running_instances(project_dirs: &ProjectDirs) -> Vec<(String, InstanceLock)> {
+    list_locks(project_dirs.data_dir())
+        .await
+        .into_iter()
+        .filter(|(_, lock)| lock.operation == LockOperation::Running)
+        .collect()
+}
+
+/// Valid locks of the instance directories in `data_dir`
+async fn list_locks(data_dir: &Path) -> Vec<(String, InstanceLock)> {
+    let mut locks = Vec::new();
+    let Ok(mut entries) = fs::read_dir(data_dir).await else {
+        return locks;
+    };
+    while let Ok(Some(entry)) = entries.next_entry().await {
+        if let Some(lock) = read_lock(&entry.path()).await {
+            locks.push((entry.file_name().to_string_lossy().to_string(), lock));
+        }
+    }
+    locks.sort_by(|(a, _), (b, _)| a.cmp(b));
+    locks
+}
+
+async fn read_lock_file(path: &Path) -> Option<InstanceLock> {
+    let content = fs::read(path).await.ok()?;
+    serde_json::from_slice(&content).ok()
+}
+
+/// Write `content` next to `path` under a name unique to this process and call, synced to disk
+async fn write_tmp_file(path: &Path, content: &[u8]) -> std::io::Result<PathBuf> {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    let tmp_path = path.with_extension(format!("lock.{}.{}.tmp", std::process::id(), COUNTER.fetch_add(1, Ordering::Relaxed)));
+    let mut file = fs::File::create(&tmp_path).await?;
+    file.write_all(content).await?;
+    file.sync_all().await?;
+    Ok(tmp_path)
+}
+
+async fn remove_if_exists(path: &Path) -> std::io::Result<()> {
+    match fs::remove_file(path).await {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    }
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|duration| duration.as_secs()).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn instances_can_only_be_locked_once() {
+        let data_dir = tempfile::tempdir().unwrap();
+        let game_dir = data_dir.path().join("minozia");
+
+        let guard = InstanceLockGuard::acquire(&game_dir, LockOperation::Running, Some("Steve".to_string())).await.unwrap();
+        let error = InstanceLockGuard::acquire(&game_dir, LockOperation::Uninstalling, None).await.unwrap_err();
+        let LockError::Locked(lock) = error else {
+            panic!("unexpected error: {}", error);
+        };
+        assert_eq!(lock.pid, std::process::id());
+        assert_eq!(lock.account.as_deref(), Some("Steve"));
+
+        let running = list_locks(data_dir.path()).await;
+        assert_eq!(running.len(), 1);
+        assert_eq!(running[0].0, "minozia");
+
+        drop(guard);
+        assert!(!game_dir.join(LOCK_FILE).exists());
+        assert!(InstanceLockGuard::acquire(&game_dir, LockOperation::Installing, None).await.is_ok());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn locks_handed_to_the_game_stay_valid() {
+        let game_dir = tempfile::tempdir().unwrap();
+        let mut guard = InstanceLockGuard::acquire(game_dir.path(), LockOperation::Running, None).await.unwrap();
+
+        // Preparing the launch takes a while before Java starts
+        tokio::time::sleep(std::time::Duration::from_millis(2100)).await;
+        let mut child = tokio::process::Command::new("sleep").arg("30").kill_on_drop(true).spawn().unwrap();
+        let pid = child.id().unwrap();
+        guard.set_pid(pid).await.unwrap();
+        tokio::time::sleep(std::time::Duration::from_millis(1100)).await;
+
+        assert_eq!(read_lock(game_dir.path()).await.map(|lock| lock.pid), Some(pid));
+        assert!(InstanceLockGuard::acquire(game_dir.path(), LockOperation::Uninstalling, None).await.is_err());
+
+        // The launcher stops before the game
+        drop(guard);
+        assert_eq!(read_lock(game_dir.path()).await.map(|lock| lock.pid), Some(pid));
+
+        child.kill().await.unwrap();
+        child.wait().await.unwrap();
+        assert!(read_lock(game_dir.path()).await.is_none());
+        let guard = InstanceLockGuard::acquire(game_dir.path(), LockOperation::Uninstalling, None).await.unwrap();
+        guard.release();
+        assert!(std::fs::read_dir(game_dir.path()).unwrap().next().is_none());
+    }
+
+    #[tokio::test]
+    async fn stale_locks_are_replaced() {
+        let game_dir = tempfile::tempdir().unwrap();
+        let stale = InstanceLock { operation: LockOperation::Running, pid: u32::MAX - 1, started_at: 0, account: None };
+        std::fs::write(game_dir.path().join(LOCK_FILE), serde_json::to_vec(&stale).unwrap()).unwrap();
+        assert!(stale.is_stale());
+        assert!(read_lock(game_dir.path()).await.is_none());
+
+        // The PID of this process, reused since the lock was written
+        let reused = InstanceLock { pid: std::process::id(), ..stale };
+        assert!(reused.is_stale());
+
+        std::fs::write(game_dir.path().join(LOCK_FILE), "{").unwrap();
+        let guard = InstanceLockGuard::acquire(game_dir.path(), LockOperation::Running, None).await.unwrap();
+        assert_eq!(read_lock(game_dir.path()).await.unwrap().pid, std::process::id());
+        drop(guard);
+    }
+}
@@ -0,0 +1,361 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use crate::java::OutputStream;
+
+/// Severity of a log event, as log4j names it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LogLevel {
+    Trace,
+    Debug,
+    Info,
+    Warn,
+    Error,
+    Fatal,
+}
+
+impl LogLevel {
+    fn parse(level: &str) -> Option<Self> {
+        match level.trim().to_ascii_uppercase().as_str() {
+            "TRACE" | "FINEST" | "FINER" => Some(LogLevel::Trace),
+            "DEBUG" | "FINE" | "CONFIG" => Some(LogLevel::Debug),
+            "INFO" => Some(LogLevel::Info),
+            "WARN" | "WARNING" => Some(LogLevel::Warn),
+            "ERROR" | "SEVERE" => Some(LogLevel::Error),
+            "FATAL" => Some(LogLevel::Fatal),
+            _ => None,
+        }
+    }
+}
+
+/// Steps of the game lifecycle recognized in its log
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Milestone {
+    /// The window is created, launchers usually hide or close themselves here
+    WindowReady,
+    /// The integrated server of a singleplayer world is starting
+    SingleplayerStarted,
+    /// The client is connecting to a server, `host, port` as the game logs it
+    JoinedServer { address: String },
+    /// The game is shutting down
+    Stopping,
+}
+
+/// A line, or a multi-line log4j event, of the game output
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LogEvent {
+    /// From the log4j event, or when the line was read for plain text
+    pub timestamp: SystemTime,
+    pub thread: Option<String>,
+    pub level: LogLevel,
+    pub logger: Option<String>,
+    pub message: String,
+    pub stream: OutputStream,
+    pub milestone: Option<Milestone>,
+}
+
+/// Turns output lines into [`LogEvent`]s.
+///
+/// With the logging configuration of the version JSON the game prints log4j `LegacyXMLLayout` events,
+/// older versions and loaders print `[12:34:56] [Render thread/INFO]: message` or free text.
+#[derive(Debug, Default)]
+pub struct LogParser {
+    stdout: StreamState,
+    stderr: StreamState,
+}
+
+/// A `<log4j:Event>` longer than this is not XML the parser can use, it is flushed as raw lines
+const MAX_PENDING_XML_LINES: usize = 500;
+const MAX_PENDING_XML_BYTES: usize = 64 * 1024;
+
+#[derive(Debug, Default)]
+struct StreamState {
+    /// Lines of a `<log4j:Event>` not closed yet
+    pending_xml: Option<String>,
+    pending_xml_lines: usize,
+    /// Thread, level and logger of the last event, for stack trace lines
+    last: Option<(Option<String>, LogLevel, Option<String>)>,
+}
+
+impl LogParser {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Parse one line, without its line terminator. `None` while a log4j event is incomplete or for blank lines.
+    pub fn parse_line(&mut self, stream: OutputStream, line: &str) -> Option<LogEvent> {
+        let state = match stream {
+            OutputStream::Stdout => &mut self.stdout,
+            OutputStream::Stderr => &mut self.stderr,
+        };
+
+        if let Some(pending) = &mut state.pending_xml {
+            pending.push('\n');
+            pending.push_str(line);
+            state.pending_xml_lines += 1;
+            if !line.contains("</log4j:Event>") {
+                if state.pending_xml_lines < MAX_PENDING_XML_LINES && pending.len() < MAX_PENDING_XML_BYTES {
+                    return None;
+                }
+                // Never closed, e.g. the game was writing to stdout with another layout mid-event
+                let raw = state.pending_xml.take().unwrap();
+                return Some(LogEvent {
+                    timestamp: SystemTime::now(),
+                    thread: None,
+                    level: get_default_level(stream),
+                    logger: None,
+                    message: raw,
+                    stream,
+                    milestone: None,
+                });
+            }
+            let xml = state.pending_xml.take().unwrap();
+            return parse_xml_event(&xml).map(|event| state.record(event, stream));
+        }
+        if line.trim_start().starts_with("<log4j:Event") {
+            if line.contains("</log4j:Event>") {
+                return parse_xml_event(line).map(|event| state.record(event, stream));
+            }
+            state.pending_xml = Some(line.to_string());
+            state.pending_xml_lines = 1;
+            return None;
+        }
+
+        if line.trim().is_empty() {
+            return None;
+        }
+        if let Some(event) = parse_plain_line(line) {
+            return Some(state.record(event, stream));
+        }
+
+        // Stack traces and other continuation lines belong to the previous event
+        let is_continuation = line.starts_with(char::is_whitespace) || line.starts_with("Caused by:");
+        let (thread, level, logger) = match (&state.last, is_continuation) {
+            (Some(last), true) => last.clone(),
+            _ => (None, get_default_level(stream), None),
+        };
+        Some(LogEvent {
+            timestamp: SystemTime::now(),
+            thread,
+            level,
+            logger,
+            message: line.to_string(),
+            stream,
+            milestone: None,
+        })
+    }
+}
+
+/// Level of the lines that do not say it
+fn get_default_level(stream: OutputStream) -> LogLevel {
+    match stream {
+        OutputStream::Stdout => LogLevel::Info,
+        OutputStream::Stderr => LogLevel::Error,
+    }
+}
+
+impl StreamState {
+    fn record(&mut self, mut event: LogEvent, stream: OutputStream) -> LogEvent {
+        event.stream = stream;
+        event.milestone = detect_milestone(&event.message);
+        self.last = Some((event.thread.clone(), event.level, event.logger.clone()));
+        event
+    }
+}
+
+/// `<log4j:Event logger="..." timestamp="..." level="..." thread="..."><log4j:Message><![CDATA[...]]></log4j:Message></log4j:Event>`
+fn parse_xml_event(xml: &str) -> Option<LogEvent> {
+    let start = xml.find("<log4j:Event")?;
+    let tag_end = start + xml[start..].find('>')?;
+    let tag = &xml[start..tag_end];
+
+    let mut message = get_element_text(xml, "log4j:Message").unwrap_or_default();
+    if let Some(throwable) = get_element_text(xml, "log4j:Throwable") {
+        message.push('\n');
+        message.push_str(throwable.trim_end());
+    }
+
+    Some(LogEvent {
+        timestamp: get_attribute(tag, "timestamp")
+            .and_then(|timestamp| timestamp.parse().ok())
+            .map(|millis| UNIX_EPOCH + Duration::from_millis(millis))
+            .unwrap_or_else(SystemTime::now),
+        thread: get_attribute(tag, "thread"),
+        level: get_attribute(tag, "level").and_then(|level| LogLevel::parse(&level)).unwrap_or(LogLevel::Info),
+        logger: get_attribute(tag, "logger"),
+        message,
+        stream: OutputStream::Stdout,
+        milestone: None,
+    })
+}
+
+fn get_attribute(tag: &str, name: &str) -> Option<String> {
+    let pattern = format!(" {}=\"", name);
+    let start = tag.find(&pattern)? + pattern.len();
+    let end = start + tag[start..].find('"')?;
+    Some(unescape_xml(&tag[start..end]))
+}
+
+fn get_element_text(xml: &str, element: &str) -> Option<String> {
+    let open = format!("<{}>", element);
+    let close = format!("</{}>", element);
+    let start = xml.find(&open)? + open.len();
+    let end = start + xml[start..].find(&close)?;
+    let text = xml[start..end].trim();
+    Some(match text.strip_prefix("<![CDATA[").and_then(|text| text.strip_suffix("]]>")) {
+        Some(cdata) => cdata.to_string(),
+        None => unescape_xml(text),
+    })
+}
+
+fn unescape_xml(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+/// `[12:34:56] [Render thread/INFO]: message`, with the `[logger]` or `(logger)` Forge and Fabric add,
+/// or `2013-09-01 12:34:56 [INFO] message` before 1.7
+fn parse_plain_line(line: &str) -> Option<LogEvent> {
+    let event = |thread: Option<&str>, level: &str, logger: Option<&str>, message: &str| {
+        Some(LogEvent {
+            timestamp: SystemTime::now(),
+            thread: thread.map(str::to_string),
+            level: LogLevel::parse(level)?,
+            logger: logger.map(str::to_string),
+            message: message.to_string(),
+            stream: OutputStream::Stdout,
+            milestone: None,
+        })
+    };
+
+    if let Some(rest) = line.strip_prefix('[') {
+        let (_time, rest) = rest.split_once("] [")?;
+        let (source, rest) = rest.split_once(']')?;
+        let (thread, level) = source.rsplit_once('/')?;
+
+        let rest = rest.trim_start();
+        let (logger, message) = if let Some(rest) = rest.strip_prefix('[') {
+            let (logger, message) = rest.split_once(']')?;
+            (Some(logger), message)
+        } else if let Some(rest) = rest.strip_prefix('(') {
+            let (logger, message) = rest.split_once(')')?;
+            (Some(logger), message)
+        } else {
+            (None, rest)
+        };
+        let message = message.strip_prefix(':').unwrap_or(message).trim_start();
+        return event(Some(thread), level, logger, message);
+    }
+
+    // The date is followed by the time, then the level
+    let (_date_time, rest) = line.split_once(" [")?;
+    if !line.as_bytes().first()?.is_ascii_digit() {
+        return None;
+    }
+    let (level, message) = rest.split_once("] ")?;
+    event(None, level, None, message)
+}
+
+fn detect_milestone(message: &str) -> Option<Milestone> {
+    // LWJGL 3 since 1.13, LWJGL 2 before
+    if message.starts_with("Backend library: LWJGL version") || message.starts_with("LWJGL Version: ") {
+        return Some(Milestone::WindowReady);
+    }
+    if message.starts_with("Starting integrated minecraft server version") {
+        return Some(Milestone::SingleplayerStarted);
+    }
+    if let Some(address) = message.strip_prefix("Connecting to ") {
+        return Some(Milestone::JoinedServer { address: address.trim().to_string() });
+    }
+    if message == "Stopping!" {
+        return Some(Milestone::Stopping);
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn xml_events_are_parsed_across_lines() {
+        let mut parser = LogParser::new();
+        let lines = [
+            r#"<log4j:Event logger="net.minecraft.client.Minecraft" timestamp="1700000000000" level="INFO" thread="Render thread">"#,
+            "  <log4j:Message><![CDATA[Backend library: LWJGL version 3.3.1 SNAPSHOT]]></log4j:Message>",
+            "</log4j:Event>",
+        ];
+        assert!(parser.parse_line(OutputStream::Stdout, lines[0]).is_none());
+        assert!(parser.parse_line(OutputStream::Stdout, lines[1]).is_none());
+        let event = parser.parse_line(OutputStream::Stdout, lines[2]).unwrap();
+
+        assert_eq!(event.timestamp, UNIX_EPOCH + Duration::from_millis(1_700_000_000_000));
+        assert_eq!(event.thread.as_deref(), Some("Render thread"));
+        assert_eq!(event.level, LogLevel::Info);
+        assert_eq!(event.logger.as_deref(), Some("net.minecraft.client.Minecraft"));
+        assert_eq!(event.message, "Backend library: LWJGL version 3.3.1 SNAPSHOT");
+        assert_eq!(event.milestone, Some(Milestone::WindowReady));
+
+        let lines = [
+            r#"<log4j:Event logger="ekx" timestamp="1700000000001" level="ERROR" thread="Render thread">"#,
+            "  <log4j:Message><![CDATA[Failed to load <model>]]></log4j:Message>",
+            "  <log4j:Throwable><![CDATA[java.io.IOException: missing",
+            "\tat ekx.a(SourceFile:42)",
+            "]]></log4j:Throwable>",
+            "</log4j:Event>",
+        ];
+        let events: Vec<_> = lines.iter().filter_map(|line| parser.parse_line(OutputStream::Stdout, line)).collect();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].level, LogLevel::Error);
+        assert_eq!(events[0].message, "Failed to load <model>\njava.io.IOException: missing\n\tat ekx.a(SourceFile:42)");
+    }
+
+    #[test]
+    fn unclosed_xml_events_are_flushed_as_raw_lines() {
+        let mut parser = LogParser::new();
+        assert!(parser.parse_line(OutputStream::Stdout, r#"<log4j:Event logger="ekx" level="INFO" thread="main">"#).is_none());
+        let events: Vec<_> = (1..MAX_PENDING_XML_LINES)
+            .filter_map(|i| parser.parse_line(OutputStream::Stdout, &format!("line {}", i)))
+            .collect();
+
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].level, LogLevel::Info);
+        assert_eq!(events[0].message.lines().count(), MAX_PENDING_XML_LINES);
+        assert!(events[0].message.ends_with(&format!("line {}", MAX_PENDING_XML_LINES - 1)));
+
+        // The parser is back to plain lines
+        let event = parser.parse_line(OutputStream::Stdout, "[12:34:56] [main/INFO]: ready").unwrap();
+        assert_eq!(event.message, "ready");
+    }
+
+    #[test]
+    fn plain_lines_are_parsed() {
+        let mut parser = LogParser::new();
+
+        let event = parser.parse_line(OutputStream::Stdout, "[12:34:56] [Render thread/INFO]: Connecting to play.example.com, 25565").unwrap();
+        assert_eq!(event.thread.as_deref(), Some("Render thread"));
+        assert_eq!(event.level, LogLevel::Info);
+        assert_eq!(event.logger, None);
+        assert_eq!(event.message, "Connecting to play.example.com, 25565");
+        assert_eq!(event.milestone, Some(Milestone::JoinedServer { address: "play.example.com, 25565".to_string() }));
+
+        let event = parser.parse_line(OutputStream::Stdout, "[12:34:56] [main/WARN] (FabricLoader/Mixin) Reference map not found").unwrap();
+        assert_eq!(event.level, LogLevel::Warn);
+        assert_eq!(event.logger.as_deref(), Some("FabricLoader/Mixin"));
+        assert_eq!(event.message, "Reference map not found");
+
+        let event = parser.parse_line(OutputStream::Stdout, "2013-09-01 12:34:56 [SEVERE] Unable to launch").unwrap();
+        assert_eq!(event.level, LogLevel::Error);
+        assert_eq!(event.message, "Unable to launch");
+
+        // Stack trace lines keep the level of the line they belong to
+        let event = parser.parse_line(OutputStream::Stdout, "\tat net.minecraft.client.main.Main.main(Main.java:42)").unwrap();
+        assert_eq!(event.level, LogLevel::Error);
+
+        let event = parser.parse_line(OutputStream::Stderr, "Exception in thread \"main\" java.lang.NoClassDefFoundError").unwrap();
+        assert_eq!(event.level, LogLevel::Error);
+        assert_eq!(event.stream, OutputStream::Stderr);
+        assert!(parser.parse_line(OutputStream::Stdout, "   ").is_none());
+    }
+}
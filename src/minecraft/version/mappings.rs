@@ -0,0 +1,216 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::path::Path;
+use tokio::fs;
+
+/// Mojang name of an obfuscated method, with the lines it spans in the obfuscated class
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct MethodMapping {
+    name: String,
+    lines: Option<(u32, u32)>,
+}
+
+#[derive(Debug, Clone, Default)]
+struct ClassMapping {
+    name: String,
+    /// Obfuscated method name to the methods sharing it, overloads often do
+    methods: HashMap<String, Vec<MethodMapping>>,
+}
+
+/// ProGuard mapping file, as published in `downloads.client_mappings` since 1.14.4
+#[derive(Debug, Clone, Default)]
+pub struct ProguardMappings {
+    /// Obfuscated class name to its mapping
+    classes: HashMap<String, ClassMapping>,
+}
+
+impl ProguardMappings {
+    pub async fn load(path: &Path) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        Ok(Self::parse(&fs::read_to_string(path).await?))
+    }
+
+    /// Parse `net.minecraft.client.Minecraft -> ekx:` class lines and their indented members
+    pub fn parse(mappings: &str) -> Self {
+        let mut classes = HashMap::new();
+        let mut current: Option<(String, ClassMapping)> = None;
+
+        for line in mappings.lines() {
+            if line.trim().is_empty() || line.trim_start().starts_with('#') {
+                continue;
+            }
+            if !line.starts_with(char::is_whitespace) {
+                if let Some((obfuscated, class)) = current.take() {
+                    classes.insert(obfuscated, class);
+                }
+                current = line
+                    .strip_suffix(':')
+                    .and_then(|line| line.split_once(" -> "))
+                    .map(|(name, obfuscated)| (obfuscated.to_string(), ClassMapping { name: name.to_string(), ..ClassMapping::default() }));
+                continue;
+            }
+
+            // `    12:15:void tick() -> a`, fields have no parentheses
+            let Some((_, class)) = &mut current else {
+                continue;
+            };
+            let Some((member, obfuscated)) = line.trim().split_once(" -> ") else {
+                continue;
+            };
+            let Some(arguments_start) = member.find('(') else {
+                continue;
+            };
+            let mut parts = member.splitn(3, ':');
+            let lines = match (parts.next(), parts.next(), parts.next()) {
+                (Some(start), Some(end), Some(_)) => start.parse().ok().zip(end.parse().ok()),
+                _ => None,
+            };
+            let signature = &member[..arguments_start];
+            let name = signature.rsplit([' ', ':']).next().unwrap_or(signature);
+            class.methods.entry(obfuscated.to_string()).or_default().push(MethodMapping { name: name.to_string(), lines });
+        }
+        if let Some((obfuscated, class)) = current {
+            classes.insert(obfuscated, class);
+        }
+        Self { classes }
+    }
+
+    /// Mojang name of an obfuscated class, e.g. `ekx` to `net.minecraft.client.Minecraft`
+    pub fn get_class_name(&self, obfuscated: &str) -> Option<&str> {
+        self.classes.get(obfuscated).map(|class| class.name.as_str())
+    }
+
+    /// Mojang name of an obfuscated method, the line number picks the right one when several share the obfuscated name
+    pub fn get_method_name(&self, class: &str, method: &str, line: Option<u32>) -> Option<&str> {
+        let candidates = self.classes.get(class)?.methods.get(method)?;
+        if let Some(line) = line
+            && let Some(candidate) = candidates.iter().find(|candidate| candidate.lines.is_some_and(|(start, end)| (start..=end).contains(&line)))
+        {
+            return Some(&candidate.name);
+        }
+        let first = &candidates.first()?.name;
+        candidates.iter().all(|candidate| &candidate.name == first).then_some(first.as_str())
+    }
+
+    /// Rewrite the stack traces of a crash report or a log with Mojang names.
+    ///
+    /// Only frames (`at ekx.a(SourceFile:42)`) and exception lines (`Caused by: ekx$b: message`) are rewritten,
+    /// obfuscated names are too short to be replaced in free text.
+    pub fn deobfuscate(&self, text: &str) -> String {
+        let mut result: Vec<String> = text.lines().map(|line| self.deobfuscate_line(line)).collect();
+        if text.ends_with('\n') {
+            result.push(String::new());
+        }
+        result.join("\n")
+    }
+
+    fn deobfuscate_line(&self, line: &str) -> String {
+        let indent = &line[..line.len() - line.trim_start().len()];
+        let trimmed = line.trim_start();
+
+        if let Some(frame) = trimmed.strip_prefix("at ") {
+            return match self.deobfuscate_frame(frame) {
+                Some(frame) => format!("{}at {}", indent, frame),
+                None => line.to_string(),
+            };
+        }
+
+        let (prefix, exception) = match trimmed.strip_prefix("Caused by: ") {
+            Some(exception) => ("Caused by: ", exception),
+            None => ("", trimmed),
+        };
+        let (class, message) = match exception.split_once(": ") {
+            Some((class, message)) => (class, Some(message)),
+            None => (exception, None),
+        };
+        match self.get_class_name(class) {
+            Some(name) => match message {
+                Some(message) => format!("{}{}{}: {}", indent, prefix, name, message),
+                None => format!("{}{}{}", indent, prefix, name),
+            },
+            None => line.to_string(),
+        }
+    }
+
+    /// `[module/]class.method(File:line)[ suffix]`
+    fn deobfuscate_frame(&self, frame: &str) -> Option<String> {
+        let open = frame.find('(')?;
+        let close = open + frame[open..].find(')')?;
+        let (module, method_path) = match frame[..open].rsplit_once('/') {
+            Some((module, method_path)) => (Some(module), method_path),
+            None => (None, &frame[..open]),
+        };
+        let (class, method) = method_path.rsplit_once('.')?;
+        let class_name = self.get_class_name(class)?;
+
+        let location = &frame[open + 1..close];
+        let line = location.rsplit_once(':').and_then(|(_, line)| line.parse().ok());
+        let method_name = self.get_method_name(class, method, line).unwrap_or(method);
+        // `SourceFile` is all the obfuscated jar knows, the file is named after the outer class
+        let location = match location.strip_prefix("SourceFile") {
+            Some(rest) => {
+                let outer = class_name.rsplit('.').next().unwrap_or(class_name);
+                format!("{}.java{}", outer.split('$').next().unwrap_or(outer), rest)
+            }
+            None => location.to_string(),
+        };
+
+        Some(format!(
+            "{}{}.{}({}){}",
+            module.map(|module| format!("{}/", module)).unwrap_or_default(),
+            class_name,
+            method_name,
+            location,
+            &frame[close + 1..],
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MAPPINGS: &str = "# {\"fileName\":\"client.txt\",\"id\":\"sourceFile\"}
+net.minecraft.client.Minecraft -> ekx:
+    net.minecraft.client.Options options -> m
+    1:1:void <init>(net.minecraft.client.main.GameConfig) -> <init>
+    100:120:void tick() -> a
+    130:140:void runTick(boolean) -> a
+    void run() -> b
+net.minecraft.client.Minecraft$ChatStatus -> ekx$a:
+    10:12:boolean isChatAllowed(boolean) -> a
+net.minecraft.ReportedException -> z:
+";
+
+    #[test]
+    fn mappings_are_parsed() {
+        let mappings = ProguardMappings::parse(MAPPINGS);
+        assert_eq!(mappings.get_class_name("ekx"), Some("net.minecraft.client.Minecraft"));
+        assert_eq!(mappings.get_class_name("ekx$a"), Some("net.minecraft.client.Minecraft$ChatStatus"));
+        assert_eq!(mappings.get_method_name("ekx", "a", Some(135)), Some("runTick"));
+        assert_eq!(mappings.get_method_name("ekx", "a", Some(110)), Some("tick"));
+        // Ambiguous without a line number
+        assert_eq!(mappings.get_method_name("ekx", "a", None), None);
+        assert_eq!(mappings.get_method_name("ekx", "b", None), Some("run"));
+        assert_eq!(mappings.get_method_name("ekx", "m", None), None);
+    }
+
+    #[test]
+    fn stack_traces_are_deobfuscated() {
+        let mappings = ProguardMappings::parse(MAPPINGS);
+        let trace = "Description: Unexpected error\n\
+            \n\
+            z: Ticking screen\n\
+            \tat ekx.a(SourceFile:135)\n\
+            \tat TRANSFORMER/minecraft@1.20.1/ekx$a.a(SourceFile:11) ~[client-1.20.1.jar:?]\n\
+            \tat net.minecraft.client.main.Main.main(Main.java:218)\n\
+            Caused by: ekx$a: a message with a word\n";
+
+        assert_eq!(mappings.deobfuscate(trace), "Description: Unexpected error\n\
+            \n\
+            net.minecraft.ReportedException: Ticking screen\n\
+            \tat net.minecraft.client.Minecraft.runTick(Minecraft.java:135)\n\
+            \tat TRANSFORMER/minecraft@1.20.1/net.minecraft.client.Minecraft$ChatStatus.isChatAllowed(Minecraft.java:11) ~[client-1.20.1.jar:?]\n\
+            \tat net.minecraft.client.main.Main.main(Main.java:218)\n\
+            Caused by: net.minecraft.client.Minecraft$ChatStatus: a message with a word\n");
+    }
+}
@@ -1,3 +1,13 @@
 pub mod version;
 pub mod loaders;
-pub mod launch;
\ No newline at end of file
+pub mod launch;
+pub mod arguments;
+pub mod process;
+pub mod options;
+pub mod logs;
+pub mod crash;
+pub mod mappings;
+pub mod hooks;
+pub mod command;
+pub mod lock;
+pub mod sessions;
\ No newline at end of file
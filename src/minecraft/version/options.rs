@@ -0,0 +1,315 @@
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use serde::{Deserialize, Serialize};
+use crate::utils::system::sys_memory;
+
+/// Errors raised by [`LaunchOptions::validate`]
+#[derive(Debug)]
+pub enum LaunchOptionsError {
+    /// The heap sizes are zero or the minimum is above the maximum
+    InvalidMemory { min_memory: u64, max_memory: u64 },
+    /// More heap is requested than the machine has, in MiB
+    NotEnoughMemory { requested: u64, available: u64 },
+    /// The width or height of the window is zero, or only one of them is set
+    InvalidResolution,
+    /// The extra arguments could not be tokenized, e.g. an unclosed quote
+    InvalidArguments(String),
+    /// The Quick Play server is not a `host[:port]` address
+    InvalidServerAddress(String),
+    /// A variable to set or remove is not a `[A-Za-z_][A-Za-z0-9_]*` name, it could not be exported by a shell
+    InvalidEnvironmentVariable(String),
+}
+
+impl Display for LaunchOptionsError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LaunchOptionsError::InvalidMemory { min_memory, max_memory } => write!(f, "Invalid heap size: {}M minimum for {}M maximum", min_memory, max_memory),
+            LaunchOptionsError::NotEnoughMemory { requested, available } => write!(f, "{}M of heap requested but the system only has {}M", requested, available),
+            LaunchOptionsError::InvalidResolution => write!(f, "The window width and height must both be set and above 0"),
+            LaunchOptionsError::InvalidArguments(arguments) => write!(f, "Invalid arguments: {}", arguments),
+            LaunchOptionsError::InvalidServerAddress(address) => write!(f, "Invalid server address: {}", address),
+            LaunchOptionsError::InvalidEnvironmentVariable(name) => write!(f, "Invalid environment variable name: {:?}", name),
+        }
+    }
+}
+
+impl Error for LaunchOptionsError {}
+
+/// Where the game goes once started instead of the title screen
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum QuickPlay {
+    /// Join a server, `host[:port]`
+    Multiplayer { address: String },
+    /// Open a world of the `saves` directory, by folder name
+    Singleplayer { world: String },
+    /// Join a Realm, by id
+    Realms { realm: String },
+}
+
+impl QuickPlay {
+    /// Feature of the version JSON enabling the matching `--quickPlay*` argument
+    pub fn get_feature(&self) -> &'static str {
+        match self {
+            QuickPlay::Multiplayer { .. } => "is_quick_play_multiplayer",
+            QuickPlay::Singleplayer { .. } => "is_quick_play_singleplayer",
+            QuickPlay::Realms { .. } => "is_quick_play_realms",
+        }
+    }
+
+    /// Host and port of the server, 25565 when the port is omitted
+    pub fn get_server(&self) -> Option<Result<(String, u16), LaunchOptionsError>> {
+        match self {
+            QuickPlay::Multiplayer { address } => Some(parse_server_address(address)),
+            _ => None,
+        }
+    }
+}
+
+fn parse_server_address(address: &str) -> Result<(String, u16), LaunchOptionsError> {
+    let invalid = || LaunchOptionsError::InvalidServerAddress(address.to_string());
+    // `[::1]:25565` for IPv6, the brackets are dropped for `--server`
+    let (host, port) = match address.strip_prefix('[') {
+        Some(rest) => {
+            let (host, rest) = rest.split_once(']').ok_or_else(invalid)?;
+            (host, rest.strip_prefix(':'))
+        }
+        None => match address.split_once(':') {
+            Some((host, port)) => (host, Some(port)),
+            None => (address, None),
+        },
+    };
+    let port = match port {
+        Some(port) => port.parse().map_err(|_| invalid())?,
+        None => 25565,
+    };
+    if host.is_empty() || host.contains(char::is_whitespace) {
+        return Err(invalid());
+    }
+    Ok((host.to_string(), port))
+}
+
+/// Per-instance settings of the game process, given to [`super::version::Version::with_launch_options`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LaunchOptions {
+    /// Initial heap (`-Xms`), in MiB
+    pub min_memory: u64,
+    /// Maximum heap (`-Xmx`), in MiB
+    pub max_memory: u64,
+    /// Added after the JVM arguments of the version, right before the main class, with shell-style quoting: `-Dfoo="a b" -XX:+UseG1GC`
+    pub jvm_arguments: String,
+    /// Added after the game arguments of the version, with the same quoting
+    pub game_arguments: String,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub fullscreen: bool,
+    /// Reported to the game as `${launcher_name}`, the crate name by default
+    pub launcher_name: Option<String>,
+    /// Reported to the game as `${launcher_version}`, the crate version by default
+    pub launcher_version: Option<String>,
+    /// Server, world or Realm to open directly
+    pub quick_play: Option<QuickPlay>,
+    /// Commands Java is started through, e.g. `["gamemoderun", "mangohud --dlsym"]`, the first one is the outermost
+    pub wrapper_commands: Vec<String>,
+    /// Variables set for the game, on top of the launcher environment
+    pub environment: BTreeMap<String, String>,
+    /// Variables of the launcher environment the game must not inherit
+    pub removed_environment: Vec<String>,
+    /// Shell command run before the game, the launch is aborted if it fails.
+    ///
+    /// Hooks are run in the game directory and see `INST_NAME`, `INST_DIR`, `INST_MC_DIR`, `INST_JAVA`,
+    /// `INST_MC_VERSION`, `INST_LOADER` and `INST_LOADER_VERSION`.
+    pub pre_launch_command: Option<String>,
+    /// Shell command run once the game exited, with `INST_EXIT_CODE` on top of the pre-launch variables
+    pub post_exit_command: Option<String>,
+}
+
+impl Default for LaunchOptions {
+    fn default() -> Self {
+        Self {
+            min_memory: 1024,
+            max_memory: 2048,
+            jvm_arguments: String::new(),
+            game_arguments: String::new(),
+            width: None,
+            height: None,
+            fullscreen: false,
+            launcher_name: None,
+            launcher_version: None,
+            quick_play: None,
+            wrapper_commands: Vec::new(),
+            environment: BTreeMap::new(),
+            removed_environment: Vec::new(),
+            pre_launch_command: None,
+            post_exit_command: None,
+        }
+    }
+}
+
+impl LaunchOptions {
+    /// Check the options against the memory of this machine
+    pub fn validate(&self) -> Result<(), LaunchOptionsError> {
+        self.validate_with_memory(sys_memory() / (1024 * 1024))
+    }
+
+    fn validate_with_memory(&self, available: u64) -> Result<(), LaunchOptionsError> {
+        if self.min_memory == 0 || self.min_memory > self.max_memory {
+            return Err(LaunchOptionsError::InvalidMemory { min_memory: self.min_memory, max_memory: self.max_memory });
+        }
+        if self.max_memory > available {
+            return Err(LaunchOptionsError::NotEnoughMemory { requested: self.max_memory, available });
+        }
+        match (self.width, self.height) {
+            (None, None) => {}
+            (Some(width), Some(height)) if width > 0 && height > 0 => {}
+            _ => return Err(LaunchOptionsError::InvalidResolution),
+        }
+        self.get_jvm_arguments()?;
+        self.get_game_arguments()?;
+        self.get_wrappers()?;
+        if let Some(Err(error)) = self.quick_play.as_ref().and_then(QuickPlay::get_server) {
+            return Err(error);
+        }
+        if let Some(name) = self.environment.keys().chain(&self.removed_environment).find(|name| !is_environment_variable_name(name)) {
+            return Err(LaunchOptionsError::InvalidEnvironmentVariable(name.clone()));
+        }
+        Ok(())
+    }
+
+    /// `-Xms` and `-Xmx` followed by the extra JVM arguments
+    pub fn get_jvm_arguments(&self) -> Result<Vec<String>, LaunchOptionsError> {
+        let mut arguments = vec![
+            format!("-Xms{}M", self.min_memory),
+            format!("-Xmx{}M", self.max_memory),
+        ];
+        arguments.extend(split_arguments(&self.jvm_arguments)?);
+        Ok(arguments)
+    }
+
+    /// The extra game arguments, with `--fullscreen` if requested
+    pub fn get_game_arguments(&self) -> Result<Vec<String>, LaunchOptionsError> {
+        let mut arguments = split_arguments(&self.game_arguments)?;
+        if self.fullscreen {
+            arguments.push("--fullscreen".to_string());
+        }
+        Ok(arguments)
+    }
+
+    /// Tokenized wrapper commands, blank ones are ignored
+    pub fn get_wrappers(&self) -> Result<Vec<Vec<String>>, LaunchOptionsError> {
+        let mut wrappers = Vec::new();
+        for wrapper in &self.wrapper_commands {
+            let wrapper = split_arguments(wrapper)?;
+            if !wrapper.is_empty() {
+                wrappers.push(wrapper);
+            }
+        }
+        Ok(wrappers)
+    }
+
+    /// `(width, height)` when the window size is set, enables `has_custom_resolution`
+    pub fn get_resolution(&self) -> Option<(u32, u32)> {
+        self.width.zip(self.height)
+    }
+}
+
+/// Names a shell script can `export` and `unset`, anything else could inject commands
+fn is_environment_variable_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars.next().is_some_and(|first| first.is_ascii_alphabetic() || first == '_')
+        && chars.all(|char| char.is_ascii_alphanumeric() || char == '_')
+}
+
+fn split_arguments(arguments: &str) -> Result<Vec<String>, LaunchOptionsError> {
+    shell_words::split(arguments).map_err(|_| LaunchOptionsError::InvalidArguments(arguments.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn extra_arguments_are_tokenized_with_quotes() {
+        let options = LaunchOptions {
+            jvm_arguments: r#"-XX:+UseG1GC -Dlauncher.title="My pack" '-Dpath=C:\Games'"#.to_string(),
+            game_arguments: "--quickPlayPath \"saves list.json\"".to_string(),
+            fullscreen: true,
+            ..LaunchOptions::default()
+        };
+
+        assert_eq!(options.get_jvm_arguments().unwrap(), vec![
+            "-Xms1024M", "-Xmx2048M", "-XX:+UseG1GC", "-Dlauncher.title=My pack", r"-Dpath=C:\Games",
+        ]);
+        assert_eq!(options.get_game_arguments().unwrap(), vec!["--quickPlayPath", "saves list.json", "--fullscreen"]);
+
+        let options = LaunchOptions { jvm_arguments: "-Dfoo=\"unclosed".to_string(), ..LaunchOptions::default() };
+        assert!(matches!(options.validate_with_memory(8192), Err(LaunchOptionsError::InvalidArguments(_))));
+    }
+
+    #[test]
+    fn wrappers_are_tokenized() {
+        let options = LaunchOptions {
+            wrapper_commands: vec!["gamemoderun".to_string(), " ".to_string(), "mangohud --dlsym".to_string()],
+            ..LaunchOptions::default()
+        };
+        assert_eq!(options.get_wrappers().unwrap(), vec![vec!["gamemoderun".to_string()], vec!["mangohud".to_string(), "--dlsym".to_string()]]);
+    }
+
+    #[test]
+    fn memory_and_resolution_are_validated() {
+        assert!(LaunchOptions::default().validate_with_memory(8192).is_ok());
+
+        let options = LaunchOptions { max_memory: 16384, ..LaunchOptions::default() };
+        assert!(matches!(
+            options.validate_with_memory(8192),
+            Err(LaunchOptionsError::NotEnoughMemory { requested: 16384, available: 8192 })
+        ));
+
+        let options = LaunchOptions { min_memory: 4096, ..LaunchOptions::default() };
+        assert!(matches!(options.validate_with_memory(8192), Err(LaunchOptionsError::InvalidMemory { .. })));
+
+        let options = LaunchOptions { width: Some(1280), ..LaunchOptions::default() };
+        assert!(matches!(options.validate_with_memory(8192), Err(LaunchOptionsError::InvalidResolution)));
+    }
+
+    #[test]
+    fn environment_variable_names_are_validated() {
+        let options = LaunchOptions {
+            environment: BTreeMap::from([("MESA_GL_VERSION_OVERRIDE".to_string(), "4.5".to_string())]),
+            removed_environment: vec!["_JAVA_OPTIONS".to_string()],
+            ..LaunchOptions::default()
+        };
+        assert!(options.validate_with_memory(8192).is_ok());
+
+        for name in ["X; rm -rf ~", "1ABC", "", "A-B", "ÉTÉ"] {
+            let options = LaunchOptions { removed_environment: vec![name.to_string()], ..LaunchOptions::default() };
+            assert!(matches!(options.validate_with_memory(8192), Err(LaunchOptionsError::InvalidEnvironmentVariable(_))), "{}", name);
+        }
+        let options = LaunchOptions { environment: BTreeMap::from([("A=B".to_string(), String::new())]), ..LaunchOptions::default() };
+        assert!(options.validate_with_memory(8192).is_err());
+    }
+
+    #[test]
+    fn server_addresses_are_parsed() {
+        let server = |address: &str| QuickPlay::Multiplayer { address: address.to_string() }.get_server().unwrap();
+        assert_eq!(server("play.example.com").unwrap(), ("play.example.com".to_string(), 25565));
+        assert_eq!(server("127.0.0.1:25566").unwrap(), ("127.0.0.1".to_string(), 25566));
+        assert_eq!(server("[::1]:25567").unwrap(), ("::1".to_string(), 25567));
+        assert!(server("play.example.com:port").is_err());
+        assert!(server(":25565").is_err());
+        assert!(QuickPlay::Realms { realm: "1234".to_string() }.get_server().is_none());
+
+        let options: LaunchOptions = serde_json::from_str(r#"{ "quick_play": { "type": "multiplayer", "address": "mc.example.com" } }"#).unwrap();
+        assert_eq!(options.quick_play.unwrap().get_feature(), "is_quick_play_multiplayer");
+    }
+
+    #[test]
+    fn missing_fields_use_the_defaults() {
+        let options: LaunchOptions = serde_json::from_str(r#"{ "max_memory": 4096, "width": 1280, "height": 720 }"#).unwrap();
+        assert_eq!(options.min_memory, 1024);
+        assert_eq!(options.max_memory, 4096);
+        assert_eq!(options.get_resolution(), Some((1280, 720)));
+    }
+}
@@ -0,0 +1,278 @@
+use std::collections::VecDeque;
+use std::error::Error;
+use std::path::PathBuf;
+use std::process::ExitStatus;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+use log::error;
+use tokio::process::Child;
+use tokio::sync::{broadcast, oneshot, watch};
+use crate::java::JavaRuntime;
+use crate::minecraft::version::crash::{analyze_exit, CrashReport};
+use crate::minecraft::version::hooks::{Hook, HookOutput};
+use crate::minecraft::version::lock::InstanceLockGuard;
+use crate::minecraft::version::sessions::{Session, SessionStore};
+use crate::minecraft::version::logs::{LogEvent, LogParser};
+
+/// Log events kept for subscribers that are slower than the game
+const OUTPUT_CAPACITY: usize = 1024;
+/// Last lines of output kept for the crash report
+const LOG_TAIL_LINES: usize = 200;
+
+/// How the game exited
+#[derive(Debug, Clone)]
+pub struct GameExit {
+    pub status: ExitStatus,
+    /// `None` when the game exited cleanly or was killed by [`GameProcess::kill`]
+    pub crash_report: Option<CrashReport>,
+    /// Result of the post-exit hook, when the instance has one
+    pub post_exit_output: Option<Result<HookOutput, String>>,
+}
+
+/// Handle on a running game, returned by [`super::launch::Launch::launch`]
+pub struct GameProcess {
+    pid: Option<u32>,
+    output: broadcast::Sender<LogEvent>,
+    /// Subscribed at spawn, handed to the first caller of [`Self::subscribe`] so the early output is not lost
+    first_output: Mutex<Option<broadcast::Receiver<LogEvent>>>,
+    exit: watch::Receiver<Option<Result<GameExit, String>>>,
+    terminator: Mutex<Option<oneshot::Sender<()>>>,
+    killed: Arc<AtomicBool>,
+    pre_launch_output: Option<HookOutput>,
+}
+
+impl GameProcess {
+    /// Watch `child` in the background, its output is parsed and forwarded to the subscribers.
+    ///
+    /// Once it exits, `game_dir` is searched for the crash reports written since the launch, then `post_exit` is run.
+    pub(crate) fn spawn(java_runtime: JavaRuntime, mut child: Child, game_dir: PathBuf, post_exit: Option<Hook>) -> Self {
+        let started_at = SystemTime::now();
+        let pid = child.id();
+        let (output, first_output) = broadcast::channel(OUTPUT_CAPACITY);
+        let (exit_sender, exit) = watch::channel(None);
+        let (terminator, terminator_receiver) = oneshot::channel();
+        let killed = Arc::new(AtomicBool::new(false));
+
+        let sender = output.clone();
+        let was_killed = killed.clone();
+        tokio::spawn(async move {
+            let mut parser = LogParser::new();
+            let mut log_tail = VecDeque::with_capacity(LOG_TAIL_LINES);
+            let on_line = |stream, line: String| {
+                let Some(event) = parser.parse_line(stream, &line) else {
+                    return;
+                };
+                // Messages rather than raw lines, the log4j XML markup would fill the tail
+                for message in event.message.lines() {
+                    if log_tail.len() == LOG_TAIL_LINES {
+                        log_tail.pop_front();
+                    }
+                    log_tail.push_back(message.to_string());
+                }
+                // No subscriber is not an error, the event is simply dropped
+                let _ = sender.send(event);
+            };
+            let result = java_runtime.handle_io(&mut child, on_line, terminator_receiver).await;
+            let exit = match result {
+                Ok(status) => {
+                    let crash_report = match was_killed.load(Ordering::SeqCst) {
+                        true => None,
+                        false => analyze_exit(&game_dir, started_at, status, log_tail.into()).await,
+                    };
+                    let post_exit_output = match post_exit {
+                        Some(mut hook) => {
+                            let exit_code = status.code().map(|code| code.to_string()).unwrap_or_default();
+                            hook.environment.insert("INST_EXIT_CODE".to_string(), exit_code);
+                            Some(hook.run().await.map_err(|e| e.to_string()))
+                        }
+                        None => None,
+                    };
+                    Ok(GameExit { status, crash_report, post_exit_output })
+                }
+                Err(e) => Err(e.to_string()),
+            };
+            let _ = exit_sender.send(Some(exit));
+        });
+
+        Self {
+            pid,
+            output,
+            first_output: Mutex::new(Some(first_output)),
+            exit,
+            terminator: Mutex::new(Some(terminator)),
+            killed,
+            pre_launch_output: None,
+        }
+    }
+
+    pub(crate) fn with_pre_launch_output(mut self, output: Option<HookOutput>) -> Self {
+        self.pre_launch_output = output;
+        self
+    }
+
+    /// Keep the instance locked until the game exits and its hooks are done, then close `session` in `sessions`
+    pub(crate) fn with_instance_lock(self, lock: InstanceLockGuard, session: Option<(SessionStore, Session)>) -> Self {
+        let mut exit = self.exit.clone();
+        tokio::spawn(async move {
+            let exit = exit.wait_for(Option::is_some).await.ok().and_then(|exit| exit.clone());
+            if let (Some((mut sessions, session)), Some(exit)) = (session, exit)
+                && let Err(e) = sessions.finish(&session, &exit).await
+            {
+                error!("Could not record the end of the session: {}", e);
+            }
+            // Released last, an open session without lock is closed as stale
+            lock.release();
+        });
+        self
+    }
+
+    /// Captured output of the pre-launch hook, when the instance has one
+    pub fn get_pre_launch_output(&self) -> Option<&HookOutput> {
+        self.pre_launch_output.as_ref()
+    }
+
+    /// PID of the Java process, `None` if the OS did not give one
+    pub fn get_pid(&self) -> Option<u32> {
+        self.pid
+    }
+
+    /// Receive the log events of the game from now on.
+    ///
+    /// The first subscriber also gets the events sent since the start, up to the last [`OUTPUT_CAPACITY`].
+    pub fn subscribe(&self) -> broadcast::Receiver<LogEvent> {
+        match self.first_output.lock().unwrap().take() {
+            Some(receiver) => receiver,
+            None => self.output.subscribe(),
+        }
+    }
+
+    /// Whether the game is still running
+    pub fn is_running(&self) -> bool {
+        self.exit.borrow().is_none()
+    }
+
+    /// Wait for the game to exit, can be called several times
+    pub async fn wait(&self) -> Result<GameExit, Box<dyn Error + Send + Sync>> {
+        let mut exit = self.exit.clone();
+        let result = exit.wait_for(Option::is_some).await?.clone();
+        Ok(result.unwrap()?)
+    }
+
+    /// Stop the game and wait for it to exit
+    pub async fn kill(&self) -> Result<GameExit, Box<dyn Error + Send + Sync>> {
+        if let Some(terminator) = self.terminator.lock().unwrap().take() {
+            self.killed.store(true, Ordering::SeqCst);
+            let _ = terminator.send(());
+        }
+        self.wait().await
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::java::OutputStream;
+    use crate::minecraft::version::crash::CrashCause;
+    use std::collections::BTreeMap;
+    use std::time::Duration;
+
+    /// `sh` stands in for Java, the runtime only needs an executable
+    async fn spawn_shell(script: &str) -> (GameProcess, tempfile::TempDir) {
+        let game_dir = tempfile::tempdir().unwrap();
+        let runtime = JavaRuntime::new("/bin/sh".into());
+        let child = runtime
+            .execute(vec!["-c".to_string(), script.to_string()], game_dir.path())
+            .await
+            .unwrap();
+        (GameProcess::spawn(runtime, child, game_dir.path().to_path_buf(), None), game_dir)
+    }
+
+    #[tokio::test]
+    async fn output_and_exit_status_are_reported() {
+        let (process, _game_dir) = spawn_shell("echo '[12:34:56] [main/INFO]: ready'; sleep 0.1; echo oops >&2; exit 3").await;
+        // Subscribed after the first line was written
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        let mut output = process.subscribe();
+        assert!(process.get_pid().is_some());
+        assert!(process.is_running());
+
+        let exit = process.wait().await.unwrap();
+        assert_eq!(exit.status.code(), Some(3));
+        assert_eq!(exit.crash_report.unwrap().log_tail, vec!["ready", "oops"]);
+        assert!(!process.is_running());
+
+        let mut received = Vec::new();
+        while let Ok(chunk) = output.try_recv() {
+            received.push(chunk);
+        }
+        assert!(received.iter().any(|event| event.stream == OutputStream::Stdout && event.message == "ready"));
+        assert!(received.iter().any(|event| event.stream == OutputStream::Stderr && event.message == "oops"));
+    }
+
+    #[tokio::test]
+    async fn crashes_are_classified() {
+        let (process, _game_dir) = spawn_shell("echo 'Exception in thread \"Render thread\" java.lang.OutOfMemoryError: Java heap space' >&2; exit 1").await;
+        let crash_report = process.wait().await.unwrap().crash_report.unwrap();
+        assert_eq!(crash_report.cause, CrashCause::OutOfMemory);
+
+        let (process, _game_dir) = spawn_shell("echo done").await;
+        assert!(process.wait().await.unwrap().crash_report.is_none());
+    }
+
+    #[tokio::test]
+    async fn log_tail_holds_messages_of_xml_events() {
+        let (process, _game_dir) = spawn_shell(
+            "echo '<log4j:Event logger=\"ekx\" timestamp=\"1714557600000\" level=\"ERROR\" thread=\"Render thread\">'; \
+            echo '  <log4j:Message><![CDATA[Unreported exception thrown!]]></log4j:Message>'; \
+            echo '  <log4j:Throwable><![CDATA[java.lang.IllegalStateException: broken'; \
+            echo '    at ekx.a(SourceFile:135)'; \
+            echo ']]></log4j:Throwable>'; \
+            echo '</log4j:Event>'; exit 1",
+        )
+        .await;
+        let crash_report = process.wait().await.unwrap().crash_report.unwrap();
+        assert_eq!(crash_report.log_tail, vec![
+            "Unreported exception thrown!",
+            "java.lang.IllegalStateException: broken",
+            "    at ekx.a(SourceFile:135)",
+        ]);
+    }
+
+    #[tokio::test]
+    async fn wrappers_environment_and_post_exit_hook_are_applied() {
+        let game_dir = tempfile::tempdir().unwrap();
+        let runtime = JavaRuntime::new("/bin/sh".into());
+        let environment = BTreeMap::from([("GREETING".to_string(), "hello".to_string())]);
+        let child = runtime
+            .execute_with(
+                vec!["-c".to_string(), "echo \"$GREETING ${HOME:-no home} wrapped=$WRAPPED\"; exit 4".to_string()],
+                game_dir.path(),
+                &[vec!["env".to_string(), "WRAPPED=1".to_string()]],
+                &environment,
+                &["HOME".to_string()],
+            )
+            .await
+            .unwrap();
+        let post_exit = Hook {
+            command: "echo \"exited with $INST_EXIT_CODE\"".to_string(),
+            environment: BTreeMap::new(),
+            directory: game_dir.path().to_path_buf(),
+            timeout: Duration::from_secs(5),
+        };
+        let process = GameProcess::spawn(runtime, child, game_dir.path().to_path_buf(), Some(post_exit));
+
+        let exit = process.wait().await.unwrap();
+        assert_eq!(exit.status.code(), Some(4));
+        assert_eq!(exit.crash_report.unwrap().log_tail, vec!["hello no home wrapped=1"]);
+        assert_eq!(exit.post_exit_output.unwrap().unwrap().stdout, "exited with 4\n");
+    }
+
+    #[tokio::test]
+    async fn kill_stops_the_game() {
+        let (process, _game_dir) = spawn_shell("sleep 30").await;
+        let exit = tokio::time::timeout(Duration::from_secs(5), process.kill()).await.unwrap().unwrap();
+        assert!(!exit.status.success());
+        assert!(exit.crash_report.is_none());
+    }
+}
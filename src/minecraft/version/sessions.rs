@@ -0,0 +1,339 @@
+use std::error::Error;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use directories::ProjectDirs;
+use serde::{Deserialize, Serialize};
+use log::error;
+use tokio::fs;
+use tokio::io::AsyncWriteExt;
+use crate::minecraft::version::lock::{is_process_alive, read_lock, LockOperation};
+use crate::minecraft::version::process::GameExit;
+
+/// Play sessions of an instance, kept in its game directory
+pub const SESSIONS_FILE: &str = "sessions.json";
+
+/// One launch of the game, from the start of Java to its exit
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Session {
+    /// Seconds since the Unix epoch
+    pub started_at: u64,
+    /// `None` while the game is running
+    pub ended_at: Option<u64>,
+    pub exit_code: Option<i32>,
+    /// Name of the account the game was launched with
+    pub account: Option<String>,
+    pub crashed: bool,
+    /// PID of Java, to tell a running game from a session left open by a launcher crash
+    #[serde(default)]
+    pub pid: Option<u32>,
+    /// The launcher stopped before the game, the end is the last write of the game log
+    #[serde(default)]
+    pub interrupted: bool,
+}
+
+impl Session {
+    pub fn is_open(&self) -> bool {
+        self.ended_at.is_none()
+    }
+
+    /// Time played, `None` while the game is running
+    pub fn get_duration(&self) -> Option<Duration> {
+        self.ended_at.map(|ended_at| Duration::from_secs(ended_at.saturating_sub(self.started_at)))
+    }
+}
+
+/// Sessions of one instance, oldest first
+#[derive(Debug, Clone)]
+pub struct SessionStore {
+    game_dir: PathBuf,
+    sessions: Vec<Session>,
+}
+
+impl SessionStore {
+    /// Read the sessions of the instance in `game_dir`, an instance never played has none
+    pub async fn open(game_dir: &Path) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        let mut store = Self { game_dir: game_dir.to_path_buf(), sessions: Vec::new() };
+        store.reload().await?;
+        Ok(store)
+    }
+
+    pub fn get_sessions(&self) -> &[Session] {
+        &self.sessions
+    }
+
+    /// Time played over every finished session
+    pub fn get_total_playtime(&self) -> Duration {
+        self.sessions.iter().filter_map(Session::get_duration).sum()
+    }
+
+    /// End of the last session, or its start while the game is running
+    pub fn get_last_played(&self) -> Option<SystemTime> {
+        self.sessions
+            .iter()
+            .map(|session| session.ended_at.unwrap_or(session.started_at))
+            .max()
+            .map(|seconds| UNIX_EPOCH + Duration::from_secs(seconds))
+    }
+
+    /// Record a session of the game started as `pid`, it stays open until [`Self::finish`]
+    pub(crate) async fn start(&mut self, account: Option<String>, pid: Option<u32>) -> Result<Session, Box<dyn Error + Send + Sync>> {
+        self.reload().await?;
+        let session = Session {
+            started_at: unix_now(),
+            ended_at: None,
+            exit_code: None,
+            account,
+            crashed: false,
+            pid,
+            interrupted: false,
+        };
+        self.sessions.push(session.clone());
+        self.save().await?;
+        Ok(session)
+    }
+
+    /// Close `session`, as returned by [`Self::start`], with how the game exited
+    pub(crate) async fn finish(&mut self, session: &Session, exit: &Result<GameExit, String>) -> Result<(), Box<dyn Error + Send + Sync>> {
+        // Other sessions may have been started or closed since this store was read
+        self.reload().await?;
+        let started_at = session.started_at;
+        let session = self
+            .sessions
+            .iter_mut()
+            .rev()
+            .find(|candidate| *candidate == session)
+            .ok_or("Session not found")?;
+        session.ended_at = Some(unix_now().max(started_at));
+        if let Ok(exit) = exit {
+            session.exit_code = exit.status.code();
+            session.crashed = exit.crash_report.is_some();
+        }
+        self.save().await
+    }
+
+    /// Close the sessions left open by a launcher that stopped before the game, the ones of a game still running are kept.
+    ///
+    /// The game log only tells when the most recent one ended, the others are closed when they started.
+    /// No session ends after the start of the next one.
+    ///
+    /// Returns the number of sessions closed.
+    pub async fn close_stale(&mut self) -> Result<usize, Box<dyn Error + Send + Sync>> {
+        self.reload().await?;
+        let running = read_lock(&self.game_dir).await.filter(|lock| lock.operation == LockOperation::Running);
+        let last_log_write = fs::metadata(self.game_dir.join("logs").join("latest.log"))
+            .await
+            .and_then(|metadata| metadata.modified())
+            .ok()
+            .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+            .map(|duration| duration.as_secs());
+
+        let stale: Vec<usize> = (0..self.sessions.len())
+            .filter(|&index| {
+                let session = &self.sessions[index];
+                session.is_open() && match session.pid {
+                    Some(pid) => !is_process_alive(pid, session.started_at),
+                    // Sessions recorded without PID can only be told apart by the lock
+                    None => running.is_none(),
+                }
+            })
+            .collect();
+        let most_recent = stale.iter().copied().max_by_key(|&index| self.sessions[index].started_at);
+
+        for &index in &stale {
+            let started_at = self.sessions[index].started_at;
+            let next_start = self.sessions.iter().map(|session| session.started_at).filter(|&start| start > started_at).min();
+            let ended_at = match Some(index) == most_recent {
+                true => last_log_write.unwrap_or(started_at),
+                false => started_at,
+            };
+            let session = &mut self.sessions[index];
+            session.ended_at = Some(ended_at.min(next_start.unwrap_or(u64::MAX)).max(started_at));
+            session.interrupted = true;
+        }
+        if !stale.is_empty() {
+            self.save().await?;
+        }
+        Ok(stale.len())
+    }
+
+    /// Read the sessions again, the file is shared with the other launches of the instance
+    async fn reload(&mut self) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let path = self.game_dir.join(SESSIONS_FILE);
+        self.sessions = match path.exists() {
+            true => serde_json::from_str(&fs::read_to_string(&path).await?)?,
+            false => Vec::new(),
+        };
+        Ok(())
+    }
+
+    /// Write to a temporary file first, a crash while writing must not lose the history
+    async fn save(&self) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let content = serde_json::to_vec_pretty(&self.sessions)?;
+        let path = self.game_dir.join(SESSIONS_FILE);
+        let tmp_path = path.with_extension("json.tmp");
+        let mut file = fs::File::create(&tmp_path).await?;
+        file.write_all(&content).await?;
+        file.sync_all().await?;
+        drop(file);
+        fs::rename(&tmp_path, &path).await?;
+        Ok(())
+    }
+}
+
+/// Sessions of every instance of the launcher, by instance name. Unreadable stores are skipped.
+pub async fn get_all_sessions(project_dirs: &ProjectDirs) -> Vec<(String, SessionStore)> {
+    list_stores(project_dirs.data_dir()).await
+}
+
+/// Close the sessions a previous run of the launcher left open, to call when the launcher starts
+pub async fn close_stale_sessions(project_dirs: &ProjectDirs) -> usize {
+    let mut closed = 0;
+    for (name, mut store) in list_stores(project_dirs.data_dir()).await {
+        match store.close_stale().await {
+            Ok(count) => closed += count,
+            Err(e) => error!("Could not close the sessions of {}: {}", name, e),
+        }
+    }
+    closed
+}
+
+async fn list_stores(data_dir: &Path) -> Vec<(String, SessionStore)> {
+    let mut stores = Vec::new();
+    let Ok(mut entries) = fs::read_dir(data_dir).await else {
+        return stores;
+    };
+    while let Ok(Some(entry)) = entries.next_entry().await {
+        if !entry.path().join(SESSIONS_FILE).exists() {
+            continue;
+        }
+        let name = entry.file_name().to_string_lossy().to_string();
+        match SessionStore::open(&entry.path()).await {
+            Ok(store) => stores.push((name, store)),
+            Err(e) => error!("Could not read the sessions of {}: {}", name, e),
+        }
+    }
+    stores.sort_by(|(a, _), (b, _)| a.cmp(b));
+    stores
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|duration| duration.as_secs()).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn session(started_at: u64, ended_at: Option<u64>) -> Session {
+        Session { started_at, ended_at, exit_code: Some(0), account: Some("Steve".to_string()), crashed: false, pid: None, interrupted: false }
+    }
+
+    #[tokio::test]
+    async fn playtime_is_summed_over_finished_sessions() {
+        let game_dir = tempfile::tempdir().unwrap();
+        let mut store = SessionStore::open(game_dir.path()).await.unwrap();
+        assert_eq!(store.get_last_played(), None);
+
+        store.sessions = vec![session(1_000, Some(1_600)), session(5_000, Some(8_600))];
+        store.save().await.unwrap();
+        let started = store.start(Some("Alex".to_string()), None).await.unwrap();
+
+        let mut store = SessionStore::open(game_dir.path()).await.unwrap();
+        assert_eq!(store.get_sessions().len(), 3);
+        assert_eq!(store.get_total_playtime(), Duration::from_secs(4_200));
+        assert_eq!(store.get_last_played(), Some(UNIX_EPOCH + Duration::from_secs(started.started_at)));
+
+        store.finish(&started, &Err("lost".to_string())).await.unwrap();
+        let store = SessionStore::open(game_dir.path()).await.unwrap();
+        let last = store.get_sessions().last().unwrap();
+        assert!(!last.is_open());
+        assert_eq!(last.account.as_deref(), Some("Alex"));
+        assert_eq!(last.exit_code, None);
+    }
+
+    #[tokio::test]
+    async fn stale_sessions_are_closed() {
+        let game_dir = tempfile::tempdir().unwrap();
+        let mut store = SessionStore::open(game_dir.path()).await.unwrap();
+        store.sessions = vec![session(1_000, Some(1_600)), session(5_000, None)];
+        store.save().await.unwrap();
+
+        // No lock: the launcher stopped and the game with it
+        assert_eq!(store.close_stale().await.unwrap(), 1);
+        let store = SessionStore::open(game_dir.path()).await.unwrap();
+        let last = store.get_sessions().last().unwrap();
+        assert!(last.interrupted);
+        assert_eq!(last.ended_at, Some(5_000));
+        assert_eq!(store.get_total_playtime(), Duration::from_secs(600));
+    }
+
+    #[tokio::test]
+    async fn only_the_most_recent_stale_session_gets_the_log_time() {
+        let game_dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(game_dir.path().join("logs")).unwrap();
+        let log = std::fs::File::create(game_dir.path().join("logs").join("latest.log")).unwrap();
+        log.set_modified(UNIX_EPOCH + Duration::from_secs(5_600)).unwrap();
+
+        let mut store = SessionStore::open(game_dir.path()).await.unwrap();
+        store.sessions = vec![session(1_000, None), session(5_000, None)];
+        store.save().await.unwrap();
+        assert_eq!(store.close_stale().await.unwrap(), 2);
+        let ended: Vec<_> = store.get_sessions().iter().map(|session| session.ended_at).collect();
+        assert_eq!(ended, vec![Some(1_000), Some(5_600)]);
+
+        // The log was written by a later launch, this one ended before it started at most
+        store.sessions = vec![session(5_000, None), session(5_200, Some(5_600))];
+        store.save().await.unwrap();
+        assert_eq!(store.close_stale().await.unwrap(), 1);
+        assert_eq!(store.get_sessions()[0].ended_at, Some(5_200));
+    }
+
+    #[tokio::test]
+    async fn finishing_keeps_the_sessions_written_meanwhile() {
+        let game_dir = tempfile::tempdir().unwrap();
+        let mut first = SessionStore::open(game_dir.path()).await.unwrap();
+        let started = first.start(None, None).await.unwrap();
+
+        // A second launch of the instance, e.g. after the first one crashed
+        let mut second = SessionStore::open(game_dir.path()).await.unwrap();
+        second.start(Some("Alex".to_string()), None).await.unwrap();
+
+        first.finish(&started, &Err("lost".to_string())).await.unwrap();
+        let store = SessionStore::open(game_dir.path()).await.unwrap();
+        assert_eq!(store.get_sessions().len(), 2);
+        assert!(!store.get_sessions()[0].is_open());
+        assert!(store.get_sessions()[1].is_open());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn sessions_of_a_running_game_are_kept_open() {
+        let game_dir = tempfile::tempdir().unwrap();
+        let mut game = tokio::process::Command::new("sleep").arg("30").kill_on_drop(true).spawn().unwrap();
+        let mut store = SessionStore::open(game_dir.path()).await.unwrap();
+        let started = store.start(None, game.id()).await.unwrap();
+
+        // As `Version::get_sessions` does while the game runs
+        let mut reopened = SessionStore::open(game_dir.path()).await.unwrap();
+        assert_eq!(reopened.close_stale().await.unwrap(), 0);
+        assert!(reopened.get_sessions()[0].is_open());
+
+        game.kill().await.unwrap();
+        store.finish(&started, &Err("killed".to_string())).await.unwrap();
+        let store = SessionStore::open(game_dir.path()).await.unwrap();
+        assert!(!store.get_sessions()[0].is_open());
+        assert!(!store.get_sessions()[0].interrupted);
+    }
+
+    #[tokio::test]
+    async fn unreadable_stores_are_skipped() {
+        let data_dir = tempfile::tempdir().unwrap();
+        for (name, content) in [("broken", "[{\"started_at\": 1"), ("minozia", "[]")] {
+            std::fs::create_dir(data_dir.path().join(name)).unwrap();
+            std::fs::write(data_dir.path().join(name).join(SESSIONS_FILE), content).unwrap();
+        }
+        let stores = list_stores(data_dir.path()).await;
+        assert_eq!(stores.len(), 1);
+        assert_eq!(stores[0].0, "minozia");
+    }
+}
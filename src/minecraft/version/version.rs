@@ -2,12 +2,16 @@ use std::error::Error;
 use std::path::{PathBuf};
 use directories::ProjectDirs;
 use once_cell::sync::Lazy;
+use tokio::sync::OnceCell;
 use version_compare;
 use crate::minecraft::version::loaders::fabric::FabricLoader;
 use crate::minecraft::version::loaders::neoforge::NeoForgeLoader;
 use crate::minecraft::version::loaders::optifine::OptifineLoader;
 use crate::minecraft::version::loaders::quilt::QuiltLoader;
 use crate::minecraft::version::loaders::vanilla::VanillaLoader;
+use crate::minecraft::version::lock::{read_lock, InstanceLock, InstanceLockGuard, LockOperation};
+use crate::minecraft::version::options::LaunchOptions;
+use crate::minecraft::version::sessions::SessionStore;
 
 #[derive(Debug)]
 pub(crate) struct Version<'a> {
@@ -15,6 +19,9 @@ pub(crate) struct Version<'a> {
     pub(crate) loader:String,
     pub(crate) loader_version:String,
     pub(crate) minecraft_version: String,
+    pub(crate) launch_options: LaunchOptions,
+    /// Main class of the merged profile, resolved on first use
+    pub(crate) main_class: OnceCell<String>,
     project_dirs: &'a Lazy<ProjectDirs>,
 }
 
@@ -22,7 +29,16 @@ impl<'a> Version<'a> {
 
     pub fn new(name: &str, loader: &str, loader_version: &str, minecraft_version: &str, project_dirs: &'a Lazy<ProjectDirs>) -> Self
     {
-        Self { name: name.to_string(), loader: loader.to_string(), loader_version: loader_version.to_string(), minecraft_version: minecraft_version.to_string(), project_dirs, }
+        Self { name: name.to_string(), loader: loader.to_string(), loader_version: loader_version.to_string(), minecraft_version: minecraft_version.to_string(), launch_options: LaunchOptions::default(), main_class: OnceCell::new(), project_dirs, }
+    }
+
+    pub fn with_launch_options(mut self, launch_options: LaunchOptions) -> Self {
+        self.launch_options = launch_options;
+        self
+    }
+
+    pub fn get_launch_options(&self) -> &LaunchOptions {
+        &self.launch_options
     }
 
     pub fn get_name(&self) -> &str {
@@ -40,7 +56,23 @@ impl<'a> Version<'a> {
     pub fn get_natives_dir(&self) -> PathBuf {
         self.get_game_dir().join("natives")
     }
+    /// Lock of the instance, `None` if nothing is running or installing it
+    pub async fn get_instance_lock(&self) -> Option<InstanceLock> {
+        read_lock(&self.get_game_dir()).await
+    }
+    /// Whether the game of this instance is running, launched by this launcher or another one
+    pub async fn is_running(&self) -> bool {
+        self.get_instance_lock().await.is_some_and(|lock| lock.operation == LockOperation::Running)
+    }
+    /// Play sessions of the instance, the ones a launcher crash left open are closed first
+    pub async fn get_sessions(&self) -> Result<SessionStore, Box<dyn Error + Send + Sync>> {
+        let mut sessions = SessionStore::open(&self.get_game_dir()).await?;
+        sessions.close_stale().await?;
+        Ok(sessions)
+    }
     pub async fn uninstall_version(&self) -> Result<(), Box<dyn Error + Send + Sync>> {
+        // Refuse to delete the files of a running game
+        let _lock = InstanceLockGuard::acquire(&self.get_game_dir(), LockOperation::Uninstalling, None).await?;
         println!("[LightyLauncher] Uninstalling: {}", self.name);
         // Remove the game directory
         if self.get_game_dir().exists() {
@@ -52,7 +84,7 @@ impl<'a> Version<'a> {
 
     // check the type of the loader and install with the correct version
     pub async fn install_version(&self) -> Result<(), Box<dyn Error + Send + Sync>> {
-
+        let _lock = InstanceLockGuard::acquire(&self.get_game_dir(), LockOperation::Installing, None).await?;
 
         match self.loader.as_ref() {
             "fabric" => {
@@ -73,14 +105,17 @@ impl<'a> Version<'a> {
             }
             "forge" => {
                 // TODO
+                return Ok(());
             }
             _ => {
                 println!(
                     "[LightyLauncher] the loader: '{}' is not a valid loader. Please check ma-documentation.fr",
                     &self.loader
                 );
+                return Ok(());
             }
         }
+        self.install_launch_files().await?;
         println!("[LightyLauncher] Installation complete for {} ", self.name);
         println!("[LightyLauncher] the Game Directory is '{:#?}'", self.get_game_dir());
         Ok(())